@@ -1,32 +1,244 @@
-use crate::MongoQuery;
-use std::collections::HashMap;
-use swc_core::common::{sync::Lrc, FileName, SourceMap, SourceMapper, Spanned};
+use crate::models::{
+    load_module_models, model_call_name, module_base, ModuleModels, ABSENT_MODULE, DEFAULT_EXPORT,
+};
+use crate::suppression::{self, Suppression};
+use crate::{
+    FileCategory, Join, Language, MongoQuery, OperationClass, ParentQuery, Projection,
+    QueryOptions, Resolution, ResolutionStatus, SortKey,
+};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use swc_core::common::comments::{Comment, Comments, SingleThreadedComments};
+use swc_core::common::{sync::Lrc, FileName, SourceFile, SourceMap, SourceMapper, Span, Spanned};
 use swc_core::ecma::ast::*;
 use swc_core::ecma::visit::{Visit, VisitWith};
-use swc_ecma_parser::{lexer::Lexer, Parser, StringInput, Syntax, TsSyntax};
-
-const QUERY_METHODS: &[&str] = &[
-    "find",
-    "findOne",
-    "findOneAndUpdate",
-    "findOneAndDelete",
-    "findOneAndReplace",
-    "updateOne",
-    "updateMany",
-    "deleteOne",
-    "deleteMany",
-    "aggregate",
-    "count",
-    "countDocuments",
-    "distinct",
+use swc_ecma_parser::{lexer::Lexer, EsSyntax, PResult, Parser, StringInput, Syntax, TsSyntax};
+
+/// Where a method expects the predicate that selects documents.
+#[derive(Debug, Clone, Copy)]
+enum FilterArg {
+    /// The method takes no filter (inserts, `bulkWrite`, `estimatedDocumentCount`).
+    None,
+    /// A filter document or aggregation pipeline at the given position.
+    Document(usize),
+    /// A Mongoose `findById*` id at the given position, which always filters on `_id`.
+    Id(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MethodSpec {
+    name: &'static str,
+    operation: OperationClass,
+    filter: FilterArg,
+    /// Position of the update, replacement or inserted document(s).
+    writes: Option<usize>,
+    /// Position of the Mongoose projection or driver options argument.
+    projection: Option<usize>,
+    /// Position of the options argument.
+    options: Option<usize>,
+}
+
+impl MethodSpec {
+    const fn writes(mut self, arg_idx: usize) -> Self {
+        self.writes = Some(arg_idx);
+        self
+    }
+
+    const fn projects(mut self, arg_idx: usize) -> Self {
+        self.projection = Some(arg_idx);
+        self
+    }
+
+    const fn options(mut self, arg_idx: usize) -> Self {
+        self.options = Some(arg_idx);
+        self
+    }
+}
+
+const fn read(name: &'static str, filter: FilterArg) -> MethodSpec {
+    MethodSpec {
+        name,
+        operation: OperationClass::Read,
+        filter,
+        writes: None,
+        projection: None,
+        options: None,
+    }
+}
+
+const fn write(name: &'static str, filter: FilterArg) -> MethodSpec {
+    MethodSpec {
+        name,
+        operation: OperationClass::Write,
+        filter,
+        writes: None,
+        projection: None,
+        options: None,
+    }
+}
+
+const fn delete(name: &'static str, filter: FilterArg) -> MethodSpec {
+    MethodSpec {
+        name,
+        operation: OperationClass::Delete,
+        filter,
+        writes: None,
+        projection: None,
+        options: None,
+    }
+}
+
+const fn insert(name: &'static str) -> MethodSpec {
+    MethodSpec {
+        name,
+        operation: OperationClass::Insert,
+        filter: FilterArg::None,
+        writes: Some(0),
+        projection: None,
+        options: Some(1),
+    }
+}
+
+/// Native driver and Mongoose collection/model methods recognized as query sites.
+const QUERY_METHODS: &[MethodSpec] = &[
+    // Reads
+    read("find", FilterArg::Document(0)).projects(1).options(2),
+    read("findOne", FilterArg::Document(0))
+        .projects(1)
+        .options(2),
+    read("findById", FilterArg::Id(0)).projects(1).options(2),
+    read("exists", FilterArg::Document(0)),
+    read("aggregate", FilterArg::Document(0)).options(1),
+    read("watch", FilterArg::Document(0)).options(1),
+    read("count", FilterArg::Document(0)).options(1),
+    read("countDocuments", FilterArg::Document(0)).options(1),
+    read("estimatedDocumentCount", FilterArg::None).options(0),
+    read("distinct", FilterArg::Document(1)).options(2),
+    // Updates and replacements
+    write("findOneAndUpdate", FilterArg::Document(0))
+        .writes(1)
+        .options(2),
+    write("findOneAndReplace", FilterArg::Document(0))
+        .writes(1)
+        .options(2),
+    write("findByIdAndUpdate", FilterArg::Id(0))
+        .writes(1)
+        .options(2),
+    // Legacy driver signature: findAndModify(query, sort, doc, options).
+    write("findAndModify", FilterArg::Document(0))
+        .writes(2)
+        .options(3),
+    write("updateOne", FilterArg::Document(0))
+        .writes(1)
+        .options(2),
+    write("updateMany", FilterArg::Document(0))
+        .writes(1)
+        .options(2),
+    write("update", FilterArg::Document(0)).writes(1).options(2),
+    write("replaceOne", FilterArg::Document(0))
+        .writes(1)
+        .options(2),
+    write("bulkWrite", FilterArg::None).options(1),
+    // Deletes
+    delete("findOneAndDelete", FilterArg::Document(0)).options(1),
+    delete("findOneAndRemove", FilterArg::Document(0)).options(1),
+    delete("findByIdAndDelete", FilterArg::Id(0)).options(1),
+    delete("findByIdAndRemove", FilterArg::Id(0)).options(1),
+    delete("deleteOne", FilterArg::Document(0)).options(1),
+    delete("deleteMany", FilterArg::Document(0)).options(1),
+    delete("remove", FilterArg::Document(0)).options(1),
+    // Inserts
+    insert("insertOne"),
+    insert("insertMany"),
+    insert("create"),
 ];
 
+/// Keys of a driver or Mongoose options object, used to tell it apart from a
+/// Mongoose projection document in the same position.
+const OPTION_KEYS: &[&str] = &[
+    "projection",
+    "sort",
+    "limit",
+    "skip",
+    "hint",
+    "collation",
+    "session",
+    "readPreference",
+    "maxTimeMS",
+    "batchSize",
+    "comment",
+    "allowDiskUse",
+    "lean",
+    "populate",
+    "upsert",
+    "arrayFilters",
+    "returnDocument",
+    "new",
+    "ordered",
+    "writeConcern",
+    "bypassDocumentValidation",
+];
+
+/// Chained cursor/query calls that keep referring to the query they follow.
+const CHAIN_MODIFIERS: &[&str] = &[
+    "select",
+    "project",
+    "sort",
+    "limit",
+    "skip",
+    "hint",
+    "collation",
+    "session",
+    "read",
+    "readPreference",
+    "maxTimeMS",
+    "batchSize",
+    "comment",
+    "allowDiskUse",
+    "lean",
+    "populate",
+    "exec",
+];
+
+/// Aggregation stages that read another collection, and the key naming it.
+const JOIN_STAGES: &[(&str, &str)] = &[
+    ("$lookup", "from"),
+    ("$graphLookup", "from"),
+    ("$unionWith", "coll"),
+];
+
+fn method_spec(name: &str) -> Option<&'static MethodSpec> {
+    QUERY_METHODS.iter().find(|spec| spec.name == name)
+}
+
 pub struct MongoQueryVisitor<'a> {
     pub queries: Vec<MongoQuery>,
     pub source_map: &'a SourceMap,
     pub file_path: String,
+    pub language: Language,
     pub model_map: HashMap<String, String>,
+    /// Model names by local binding: `const User = model('User', …)`, or a
+    /// model imported or required from a project module that exports one.
+    pub local_models: HashMap<String, String>,
+    /// Whether plain identifiers may name models. Off in files that inject
+    /// models with `@InjectModel`, whose queries go through `this.<model>`.
+    pub resolve_local_models: bool,
+    /// Project modules read to resolve imported models, with their content
+    /// hashes.
+    pub dependencies: BTreeMap<String, String>,
+    /// Models exported by each module specifier imported so far.
+    imported_modules: HashMap<String, Option<ModuleModels>>,
     pub local_variables: HashMap<String, ObjectLit>,
+    pub local_arrays: HashMap<String, ArrayLit>,
+    /// Index into `queries` for each recorded query call, so chained
+    /// modifiers such as `.select()` can be attached to it.
+    query_spans: HashMap<Span, usize>,
+    /// Class currently being visited.
+    class_name: Option<String>,
+    /// Innermost named function or method currently being visited.
+    function_name: Option<String>,
 }
 
 impl<'a> MongoQueryVisitor<'a> {
@@ -34,12 +246,70 @@ impl<'a> MongoQueryVisitor<'a> {
         Self {
             queries: Vec::new(),
             source_map,
+            language: Language::of(&file_path).unwrap_or_default(),
             file_path,
             model_map: HashMap::new(),
+            local_models: HashMap::new(),
+            resolve_local_models: true,
+            dependencies: BTreeMap::new(),
+            imported_modules: HashMap::new(),
             local_variables: HashMap::new(),
+            local_arrays: HashMap::new(),
+            query_spans: HashMap::new(),
+            class_name: None,
+            function_name: None,
         }
     }
 
+    /// Binds `local` to the model that `specifier`, a project module, exports
+    /// as `export`, if it exports one there.
+    fn bind_imported_model(&mut self, specifier: &str, export: &str, local: String) {
+        if !self.imported_modules.contains_key(specifier) {
+            let from = Path::new(&self.file_path);
+            let models = load_module_models(from, specifier);
+            match (&models, module_base(from, specifier)) {
+                (Some(models), _) => {
+                    self.dependencies.insert(
+                        models.path.to_string_lossy().into_owned(),
+                        models.hash.clone(),
+                    );
+                }
+                (None, Some(base)) => {
+                    self.dependencies.insert(
+                        base.to_string_lossy().into_owned(),
+                        ABSENT_MODULE.to_string(),
+                    );
+                }
+                (None, None) => {}
+            }
+            self.imported_modules.insert(specifier.to_string(), models);
+        }
+        let model = self.imported_modules[specifier]
+            .as_ref()
+            .and_then(|models| models.exports.get(export));
+        if let Some(model) = model {
+            self.local_models.insert(local, model.clone());
+        }
+    }
+
+    /// Visits `class` with `class_name` set to `name`, or left as is for an
+    /// anonymous class, restoring the enclosing class afterwards.
+    fn visit_in_class(&mut self, name: Option<String>, class: &Class) {
+        let name = name.or_else(|| self.class_name.clone());
+        let enclosing = std::mem::replace(&mut self.class_name, name);
+        class.visit_with(self);
+        self.class_name = enclosing;
+    }
+
+    /// Visits `node` with `function_name` set to `name`, restoring the
+    /// enclosing function afterwards.
+    fn visit_in_function<N: VisitWith<Self>>(&mut self, name: Option<String>, node: &N) {
+        let name = name.or_else(|| self.function_name.clone());
+        let enclosing = std::mem::replace(&mut self.function_name, name);
+        node.visit_children_with(self);
+        self.function_name = enclosing;
+    }
+
     fn extract_fields(&self, obj: &ObjectLit) -> Vec<String> {
         let mut fields = Vec::new();
         self.extract_fields_recursive(obj, &mut fields);
@@ -48,6 +318,33 @@ impl<'a> MongoQueryVisitor<'a> {
         fields
     }
 
+    fn extract_arg_fields(&self, expr: &Expr) -> Vec<String> {
+        let mut fields = Vec::new();
+        match expr {
+            Expr::Object(obj) => fields.extend(self.extract_fields(obj)),
+            Expr::Ident(ident) => {
+                if let Some(obj) = self.local_variables.get(ident.sym.as_str()) {
+                    fields.extend(self.extract_fields(obj));
+                } else if let Some(arr) = self.local_arrays.get(ident.sym.as_str()) {
+                    for elem in arr.elems.iter().flatten() {
+                        if let Expr::Object(obj) = &*elem.expr {
+                            fields.extend(self.extract_fields(obj));
+                        }
+                    }
+                }
+            }
+            Expr::Array(arr) => {
+                for elem in arr.elems.iter().flatten() {
+                    if let Expr::Object(obj) = &*elem.expr {
+                        fields.extend(self.extract_fields(obj));
+                    }
+                }
+            }
+            _ => {}
+        }
+        fields
+    }
+
     fn extract_fields_recursive(&self, obj: &ObjectLit, fields: &mut Vec<String>) {
         for prop in &obj.props {
             if let PropOrSpread::Prop(prop) = prop {
@@ -82,7 +379,7 @@ impl<'a> MongoQueryVisitor<'a> {
         }
     }
 
-    fn analyze_callee(&self, callee: &Callee) -> Option<(String, String)> {
+    fn analyze_callee(&self, callee: &Callee) -> Option<(String, &'static MethodSpec)> {
         let Callee::Expr(expr) = callee else {
             return None;
         };
@@ -91,12 +388,10 @@ impl<'a> MongoQueryVisitor<'a> {
         };
 
         let method_name = get_member_prop_name(&member_expr.prop)?;
-        if !QUERY_METHODS.contains(&method_name.as_str()) {
-            return None;
-        }
+        let spec = method_spec(&method_name)?;
 
         let collection = self.resolve_collection(&member_expr.obj)?;
-        Some((collection, method_name))
+        Some((collection, spec))
     }
 
     fn resolve_collection(&self, expr: &Expr) -> Option<String> {
@@ -133,12 +428,624 @@ impl<'a> MongoQueryVisitor<'a> {
                 }
                 None
             }
+            Expr::Ident(ident) if self.resolve_local_models => {
+                self.local_models.get(ident.sym.as_str()).cloned()
+            }
             _ => None,
         }
     }
+
+    /// Collects the paths written by an update document, replacement document,
+    /// update pipeline or inserted document(s).
+    fn extract_written_fields(&self, expr: &Expr) -> Vec<String> {
+        let mut fields = Vec::new();
+        if let Some(obj) = self.resolve_object(expr) {
+            collect_written_fields(obj, &mut fields);
+        } else if let Some(arr) = self.resolve_array(expr) {
+            for elem in arr.elems.iter().flatten() {
+                if let Some(obj) = self.resolve_object(&elem.expr) {
+                    collect_written_fields(obj, &mut fields);
+                }
+            }
+        }
+        fields.sort();
+        fields.dedup();
+        fields
+    }
+
+    /// Reads a Mongoose projection (`'a b -c'` or `{ a: 1 }`) or a driver
+    /// options object carrying `projection`. A projection listing no fields
+    /// returns full documents, so it is `None`.
+    fn extract_projection(&self, expr: &Expr) -> Option<Projection> {
+        let projection = if let Expr::Lit(Lit::Str(select)) = expr {
+            parse_select_string(select.value.as_str().unwrap_or_default())
+        } else {
+            let obj = self.resolve_object(expr)?;
+            if is_options_object(obj) {
+                find_prop_value(obj, "projection")
+                    .and_then(|projection| self.resolve_object(projection))
+                    .map(parse_projection_object)?
+            } else {
+                parse_projection_object(obj)
+            }
+        };
+        (!projection.is_empty()).then_some(projection)
+    }
+
+    /// Reads the behavior-relevant keys of an options object into `options`.
+    /// Keys that are not options (such as a bulk operation's `filter`) are ignored.
+    fn extract_options(&self, expr: &Expr, options: &mut QueryOptions) {
+        if let Some(obj) = self.resolve_object(expr) {
+            self.extract_object_options(obj, options);
+        }
+    }
+
+    fn extract_object_options(&self, obj: &ObjectLit, options: &mut QueryOptions) {
+        for (key, value) in object_entries(obj) {
+            let Some(value) = value else {
+                // Shorthand `{ session }`.
+                if key == "session" {
+                    options.session = true;
+                }
+                continue;
+            };
+            self.apply_option(&key, value, options);
+        }
+    }
+
+    /// Applies one option, given either as an options key or as the argument of
+    /// the equivalent chained modifier, so both spellings agree.
+    fn apply_option(&self, name: &str, value: &Expr, options: &mut QueryOptions) {
+        match name {
+            "sort" => options.sort = self.extract_sort(value),
+            "limit" => options.limit = Some(self.option_value(value)),
+            "skip" => options.skip = Some(self.option_value(value)),
+            "hint" => options.hint = Some(self.option_value(value)),
+            "collation" => {
+                let locale = self
+                    .resolve_object(value)
+                    .and_then(|collation| find_prop_value(collation, "locale"));
+                options.collation = Some(self.option_value(locale.unwrap_or(value)));
+            }
+            "session" => options.session = true,
+            "read" | "readPreference" => options.read_preference = Some(self.option_value(value)),
+            "maxTimeMS" => options.max_time_ms = Some(self.option_value(value)),
+            "upsert" => options.upsert = !matches!(value, Expr::Lit(Lit::Bool(b)) if !b.value),
+            "arrayFilters" => {
+                if let Some(filters) = self.resolve_array(value) {
+                    for filter in filters.elems.iter().flatten() {
+                        let Some(filter) = self.resolve_object(&filter.expr) else {
+                            continue;
+                        };
+                        for (key, _) in object_entries(filter) {
+                            let identifier = key.split('.').next().unwrap_or_default();
+                            options.array_filters.push(identifier.to_string());
+                        }
+                    }
+                    options.array_filters.sort();
+                    options.array_filters.dedup();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Reads `{ a: 1, b: -1 }`, `'a -b'` and `[['a', 1], ['b', -1]]` sort specs.
+    fn extract_sort(&self, expr: &Expr) -> Vec<SortKey> {
+        if let Expr::Lit(Lit::Str(spec)) = expr {
+            return spec
+                .value
+                .as_str()
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(|token| match token.strip_prefix('-') {
+                    Some(field) => SortKey {
+                        field: field.to_string(),
+                        order: -1,
+                    },
+                    None => SortKey {
+                        field: token.trim_start_matches('+').to_string(),
+                        order: 1,
+                    },
+                })
+                .collect();
+        }
+        if let Some(obj) = self.resolve_object(expr) {
+            return object_entries(obj)
+                .into_iter()
+                .map(|(field, order)| SortKey {
+                    field,
+                    order: order.map_or(1, sort_order),
+                })
+                .collect();
+        }
+        if let Some(pairs) = self.resolve_array(expr) {
+            return pairs
+                .elems
+                .iter()
+                .flatten()
+                .filter_map(|pair| {
+                    let Expr::Array(pair) = &*pair.expr else {
+                        return None;
+                    };
+                    let mut elems = pair.elems.iter().flatten();
+                    let Expr::Lit(Lit::Str(field)) = &*elems.next()?.expr else {
+                        return None;
+                    };
+                    Some(SortKey {
+                        field: field.value.as_str().unwrap_or_default().to_string(),
+                        order: elems.next().map_or(1, |order| sort_order(&order.expr)),
+                    })
+                })
+                .collect();
+        }
+        Vec::new()
+    }
+
+    /// String literals are unquoted; anything else is kept as written.
+    fn option_value(&self, expr: &Expr) -> String {
+        match expr {
+            Expr::Lit(Lit::Str(s)) => s.value.as_str().unwrap_or_default().to_string(),
+            _ => self
+                .source_map
+                .span_to_snippet(expr.span())
+                .unwrap_or_else(|_| "...".to_string()),
+        }
+    }
+
+    /// Filter terms of a filter document, or of the `$match` stages of a
+    /// pipeline.
+    fn extract_predicate(&self, expr: &Expr) -> Vec<String> {
+        let mut terms = Vec::new();
+        if let Some(obj) = self.resolve_object(expr) {
+            predicate_terms(obj, &mut terms);
+        } else if let Some(pipeline) = self.resolve_array(expr) {
+            for stage in pipeline.elems.iter().flatten() {
+                if let Some(filter) = self
+                    .resolve_object(&stage.expr)
+                    .and_then(|stage| find_prop_value(stage, "$match"))
+                    .and_then(|filter| self.resolve_object(filter))
+                {
+                    predicate_terms(filter, &mut terms);
+                }
+            }
+        }
+        terms.sort();
+        terms.dedup();
+        terms
+    }
+
+    fn extract_pipeline_stages(&self, expr: &Expr) -> Vec<String> {
+        let Some(pipeline) = self.resolve_array(expr) else {
+            return Vec::new();
+        };
+        pipeline
+            .elems
+            .iter()
+            .flatten()
+            .filter_map(|stage| {
+                let stage = self.resolve_object(&stage.expr)?;
+                object_entries(stage)
+                    .into_iter()
+                    .map(|(name, _)| name)
+                    .find(|name| name.starts_with('$'))
+            })
+            .collect()
+    }
+
+    /// Returns the fields kept by the last `$project` stage of a pipeline.
+    fn extract_pipeline_projection(&self, expr: &Expr) -> Option<Projection> {
+        let pipeline = self.resolve_array(expr)?;
+        pipeline.elems.iter().flatten().rev().find_map(|stage| {
+            let stage = self.resolve_object(&stage.expr)?;
+            let project = find_prop_value(stage, "$project")?;
+            self.resolve_object(project).map(parse_projection_object)
+        })
+    }
+
+    /// Collections read by `$lookup`, `$graphLookup` and `$unionWith` stages,
+    /// including those nested in `$lookup` sub-pipelines.
+    fn extract_pipeline_joins(&self, expr: &Expr, joins: &mut Vec<Join>) {
+        let Some(pipeline) = self.resolve_array(expr) else {
+            return;
+        };
+        for stage in pipeline.elems.iter().flatten() {
+            let Some(stage) = self.resolve_object(&stage.expr) else {
+                continue;
+            };
+            for (stage_name, key) in JOIN_STAGES {
+                let Some(body) = find_prop_value(stage, stage_name) else {
+                    continue;
+                };
+                let from = string_value(body).or_else(|| {
+                    self.resolve_object(body)
+                        .and_then(|body| find_prop_value(body, key))
+                        .and_then(string_value)
+                });
+                if let Some(from) = from {
+                    joins.push(Join {
+                        from: Some(from),
+                        path: None,
+                        via: stage_name.to_string(),
+                    });
+                }
+                if let Some(sub_pipeline) = self
+                    .resolve_object(body)
+                    .and_then(|body| find_prop_value(body, "pipeline"))
+                {
+                    self.extract_pipeline_joins(sub_pipeline, joins);
+                }
+            }
+        }
+    }
+
+    /// Targets of a Mongoose `populate()` argument: a path string, an array of
+    /// them, or `{ path, model }` objects. Only an explicit `model` says which
+    /// collection is read.
+    fn extract_populate_joins(&self, expr: &Expr, joins: &mut Vec<Join>) {
+        if let Some(paths) = self.resolve_array(expr) {
+            for elem in paths.elems.iter().flatten() {
+                self.extract_populate_joins(&elem.expr, joins);
+            }
+            return;
+        }
+        let (paths, model) = match self.resolve_object(expr) {
+            Some(obj) => (
+                find_prop_value(obj, "path").and_then(string_value),
+                find_prop_value(obj, "model").and_then(string_value),
+            ),
+            None => (string_value(expr), None),
+        };
+        let paths: Vec<Option<String>> = match &paths {
+            Some(paths) => paths
+                .split_whitespace()
+                .map(|path| Some(path.to_string()))
+                .collect(),
+            None => vec![None],
+        };
+        for path in paths {
+            if path.is_none() && model.is_none() {
+                continue;
+            }
+            joins.push(Join {
+                from: model.clone(),
+                path,
+                via: "populate".to_string(),
+            });
+        }
+    }
+
+    /// Follows `.select()`, `.lean()` and similar calls back to the query
+    /// call they are chained on.
+    fn chained_query(&self, expr: &Expr) -> Option<usize> {
+        let Expr::Call(call) = expr else {
+            return None;
+        };
+        if let Some(&index) = self.query_spans.get(&call.span) {
+            return Some(index);
+        }
+        let Callee::Expr(callee) = &call.callee else {
+            return None;
+        };
+        let Expr::Member(member) = &**callee else {
+            return None;
+        };
+        let name = get_member_prop_name(&member.prop)?;
+        if !CHAIN_MODIFIERS.contains(&name.as_str()) {
+            return None;
+        }
+        self.chained_query(&member.obj)
+    }
+
+    fn apply_chained_modifier(&mut self, n: &CallExpr) {
+        let Callee::Expr(callee) = &n.callee else {
+            return;
+        };
+        let Expr::Member(member) = &**callee else {
+            return;
+        };
+        let Some(name) = get_member_prop_name(&member.prop) else {
+            return;
+        };
+        if !CHAIN_MODIFIERS.contains(&name.as_str()) {
+            return;
+        }
+        let Some(index) = self.chained_query(&member.obj) else {
+            return;
+        };
+
+        if name == "select" || name == "project" {
+            let projection = n
+                .args
+                .first()
+                .and_then(|arg| self.extract_projection(&arg.expr));
+            if let Some(projection) = projection {
+                self.queries[index].projection = Some(projection);
+            }
+        } else if name == "populate" {
+            let mut joins = Vec::new();
+            for arg in &n.args {
+                self.extract_populate_joins(&arg.expr, &mut joins);
+            }
+            self.queries[index].joins.extend(joins);
+        } else if let Some(arg) = n.args.first() {
+            let mut options = std::mem::take(&mut self.queries[index].options);
+            self.apply_option(&name, &arg.expr, &mut options);
+            self.queries[index].options = options;
+        } else if name == "session" {
+            self.queries[index].options.session = true;
+        }
+    }
+
+    /// 1-based `(line, column)` of the start and (exclusive) end of a span.
+    fn span_bounds(&self, span: Span) -> ((usize, usize), (usize, usize)) {
+        let lo = self.source_map.lookup_char_pos(span.lo);
+        let hi = self.source_map.lookup_char_pos(span.hi);
+        ((lo.line, lo.col.0 + 1), (hi.line, hi.col.0 + 1))
+    }
+
+    fn resolve_object<'b>(&'b self, expr: &'b Expr) -> Option<&'b ObjectLit> {
+        match expr {
+            Expr::Object(obj) => Some(obj),
+            Expr::Ident(ident) => self.local_variables.get(ident.sym.as_str()),
+            _ => None,
+        }
+    }
+
+    fn resolve_array<'b>(&'b self, expr: &'b Expr) -> Option<&'b ArrayLit> {
+        match expr {
+            Expr::Array(arr) => Some(arr),
+            Expr::Ident(ident) => self.local_arrays.get(ident.sym.as_str()),
+            _ => None,
+        }
+    }
+
+    /// How much of the filter (or pipeline, or `bulkWrite` operations) and
+    /// written documents of a call to `spec` could be read.
+    fn resolution(&self, spec: &MethodSpec, args: &[ExprOrSpread]) -> Resolution {
+        if args.iter().any(|arg| arg.spread.is_some()) {
+            return Resolution {
+                status: ResolutionStatus::Unresolved,
+                reasons: vec!["arguments are spread from an array".to_string()],
+            };
+        }
+
+        let primary = match spec.filter {
+            FilterArg::Document(arg_idx) => {
+                let role = if spec.name == "aggregate" {
+                    "pipeline"
+                } else {
+                    "filter"
+                };
+                args.get(arg_idx).map(|arg| (&*arg.expr, role))
+            }
+            FilterArg::None if spec.name == "bulkWrite" => {
+                args.first().map(|arg| (&*arg.expr, "operations"))
+            }
+            _ => None,
+        };
+        let written = spec
+            .writes
+            .and_then(|arg_idx| args.get(arg_idx))
+            .map(|arg| (&*arg.expr, written_role(spec.operation)));
+        self.resolution_of(primary, written)
+    }
+
+    /// Resolution of a query given its `(argument, role)` pairs: an unreadable
+    /// primary argument (the filter) leaves the query unresolved, anything
+    /// else unread makes it partial.
+    fn resolution_of(
+        &self,
+        primary: Option<(&Expr, &str)>,
+        written: Option<(&Expr, &str)>,
+    ) -> Resolution {
+        let mut status = ResolutionStatus::Resolved;
+        let mut reasons = Vec::new();
+        match primary.map(|(expr, role)| self.unread_parts(expr, role)) {
+            Some(Err(reason)) => {
+                status = ResolutionStatus::Unresolved;
+                reasons.push(reason);
+            }
+            Some(Ok(unread)) => reasons.extend(unread),
+            None => {}
+        }
+        match written.map(|(expr, role)| self.unread_parts(expr, role)) {
+            Some(Err(reason)) => reasons.push(reason),
+            Some(Ok(unread)) => reasons.extend(unread),
+            None => {}
+        }
+
+        if status == ResolutionStatus::Resolved && !reasons.is_empty() {
+            status = ResolutionStatus::Partial;
+        }
+        reasons.dedup();
+        Resolution { status, reasons }
+    }
+
+    /// What could not be read of `expr`, the `role` argument of a query, or
+    /// `Err` with the reason when none of it could.
+    fn unread_parts(&self, expr: &Expr, role: &str) -> Result<Vec<String>, String> {
+        if let Some(obj) = self.resolve_object(expr) {
+            return Ok(unread_props(obj, role));
+        }
+        let Some(arr) = self.resolve_array(expr) else {
+            return Err(format!("{} {}", role, opaque_reason(expr)));
+        };
+
+        let mut reasons = Vec::new();
+        for elem in arr.elems.iter().flatten() {
+            if elem.spread.is_some() {
+                reasons.push(format!("{} spreads another array", role));
+            } else if let Some(obj) = self.resolve_object(&elem.expr) {
+                reasons.extend(unread_props(obj, role));
+            } else {
+                reasons.push(format!(
+                    "{} has an element that is not an object literal",
+                    role
+                ));
+            }
+        }
+        Ok(reasons)
+    }
+
+    /// Turns every `{ <operation>: { filter, ... } }` entry of a `bulkWrite`
+    /// operations array into its own query, linked back to the bulk call.
+    fn expand_bulk_write(&mut self, collection: &str, parent_line: usize, operations: &Expr) {
+        let Some(operations) = self.resolve_array(operations) else {
+            return;
+        };
+
+        let mut expanded = Vec::new();
+        for (index, elem) in operations.elems.iter().enumerate() {
+            let Some(elem) = elem else {
+                continue;
+            };
+            let Some(entry) = self.resolve_object(&elem.expr) else {
+                continue;
+            };
+            let Some((operation_name, body)) = entry.props.iter().find_map(|prop| {
+                let PropOrSpread::Prop(prop) = prop else {
+                    return None;
+                };
+                let Prop::KeyValue(kv) = &**prop else {
+                    return None;
+                };
+                Some((get_prop_key(&kv.key)?, &*kv.value))
+            }) else {
+                continue;
+            };
+            let Some((operation, document_key)) = bulk_operation(&operation_name) else {
+                continue;
+            };
+
+            let body_expr = body;
+            let body = self.resolve_object(body_expr);
+            let filter = body.and_then(|body| bulk_operation_arg(body, "filter"));
+            let filter = filter.as_deref();
+            let document = body
+                .zip(document_key)
+                .and_then(|(body, key)| bulk_operation_arg(body, key));
+            let written_fields = document
+                .as_deref()
+                .map(|document| self.extract_written_fields(document))
+                .unwrap_or_default();
+            let resolution = if body.is_some() {
+                self.resolution_of(
+                    filter.map(|filter| (filter, "filter")),
+                    document
+                        .as_deref()
+                        .map(|document| (document, written_role(operation))),
+                )
+            } else {
+                Resolution {
+                    status: ResolutionStatus::Unresolved,
+                    reasons: vec![format!(
+                        "{} operation {}",
+                        operation_name,
+                        opaque_reason(body_expr)
+                    )],
+                }
+            };
+
+            let mut fields = filter
+                .map(|filter| self.extract_arg_fields(filter))
+                .unwrap_or_default();
+            let filter_terms = filter
+                .map(|filter| self.extract_predicate(filter))
+                .unwrap_or_default();
+            fields.sort();
+            fields.dedup();
+
+            let predicate = filter
+                .map(|filter| {
+                    self.source_map
+                        .span_to_snippet(filter.span())
+                        .unwrap_or_else(|_| "...".to_string())
+                })
+                .unwrap_or_default();
+
+            let mut options = QueryOptions::default();
+            if let Some(body) = body {
+                self.extract_object_options(body, &mut options);
+            }
+
+            let (start, end) = self.span_bounds(elem.expr.span());
+
+            expanded.push(MongoQuery {
+                file: self.file_path.clone(),
+                line: start.0,
+                column: start.1,
+                end_line: end.0,
+                end_column: end.1,
+                collection: collection.to_string(),
+                method: operation_name.clone(),
+                operation,
+                query_fields: fields,
+                predicate: filter_terms,
+                pipeline: Vec::new(),
+                written_fields,
+                projection: None,
+                options,
+                raw_match: format!(
+                    "{}.bulkWrite[{}].{}({})",
+                    collection, index, operation_name, predicate
+                ),
+                parent: Some(ParentQuery {
+                    method: "bulkWrite".to_string(),
+                    line: parent_line,
+                    index,
+                }),
+                class_name: self.class_name.clone(),
+                function_name: self.function_name.clone(),
+                joins: Vec::new(),
+                category: FileCategory::Source,
+                language: self.language,
+                resolution,
+            });
+        }
+
+        self.queries.extend(expanded);
+    }
 }
 
 impl<'a> Visit for MongoQueryVisitor<'a> {
+    fn visit_class_decl(&mut self, n: &ClassDecl) {
+        self.visit_in_class(Some(n.ident.sym.to_string()), &n.class);
+    }
+
+    /// Named class expressions, including `export default class X {}`.
+    /// Anonymous ones bound to a variable are named in
+    /// [`Self::visit_var_declarator`].
+    fn visit_class_expr(&mut self, n: &ClassExpr) {
+        let name = n.ident.as_ref().map(|ident| ident.sym.to_string());
+        self.visit_in_class(name, &n.class);
+    }
+
+    fn visit_class_method(&mut self, n: &ClassMethod) {
+        self.visit_in_function(get_prop_key(&n.key), n);
+    }
+
+    fn visit_fn_decl(&mut self, n: &FnDecl) {
+        self.visit_in_function(Some(n.ident.sym.to_string()), n);
+    }
+
+    fn visit_var_declarator(&mut self, n: &VarDeclarator) {
+        if let (Pat::Ident(binding), Some(Expr::Class(class))) = (&n.name, n.init.as_deref()) {
+            let name = class.ident.as_ref().unwrap_or(&binding.id).sym.to_string();
+            self.visit_in_class(Some(name), &class.class);
+            return;
+        }
+        let is_function = n
+            .init
+            .as_deref()
+            .is_some_and(|init| matches!(init, Expr::Arrow(_) | Expr::Fn(_)));
+        let name = match &n.name {
+            Pat::Ident(binding) if is_function => Some(binding.id.sym.to_string()),
+            _ => None,
+        };
+        self.visit_in_function(name, n);
+    }
+
     fn visit_constructor(&mut self, n: &Constructor) {
         for prop in n.params.iter().filter_map(|p| match p {
             ParamOrTsParamProp::TsParamProp(prop) => Some(prop),
@@ -158,57 +1065,107 @@ impl<'a> Visit for MongoQueryVisitor<'a> {
 
     fn visit_var_decl(&mut self, n: &VarDecl) {
         for decl in &n.decls {
+            let init = decl.init.as_deref();
+            if let (Some(model), Pat::Ident(binding)) = (init.and_then(model_call_name), &decl.name)
+            {
+                self.local_models.insert(binding.id.sym.to_string(), model);
+            }
+            if let Some((specifier, member)) = init.and_then(required_module) {
+                for (export, local) in required_bindings(&decl.name, member) {
+                    self.bind_imported_model(&specifier, &export, local);
+                }
+            }
             if let (Some(init), Pat::Ident(binding)) = (&decl.init, &decl.name) {
-                if let Expr::Object(obj) = &**init {
-                    self.local_variables
-                        .insert(binding.id.sym.as_str().to_string(), obj.clone());
+                match &**init {
+                    Expr::Object(obj) => {
+                        self.local_variables
+                            .insert(binding.id.sym.as_str().to_string(), obj.clone());
+                    }
+                    Expr::Array(arr) => {
+                        self.local_arrays
+                            .insert(binding.id.sym.as_str().to_string(), arr.clone());
+                    }
+                    _ => {}
                 }
             }
         }
         n.visit_children_with(self);
     }
 
+    fn visit_import_decl(&mut self, n: &ImportDecl) {
+        let Some(specifier) = n.src.value.as_str().filter(|src| src.starts_with('.')) else {
+            return;
+        };
+        for import in &n.specifiers {
+            let (export, local) = match import {
+                ImportSpecifier::Named(named) => match &named.imported {
+                    Some(ModuleExportName::Ident(imported)) => {
+                        (imported.sym.to_string(), &named.local)
+                    }
+                    Some(ModuleExportName::Str(imported)) => (
+                        imported.value.as_str().unwrap_or_default().to_string(),
+                        &named.local,
+                    ),
+                    None => (named.local.sym.to_string(), &named.local),
+                },
+                ImportSpecifier::Default(default) => (DEFAULT_EXPORT.to_string(), &default.local),
+                ImportSpecifier::Namespace(_) => continue,
+            };
+            self.bind_imported_model(specifier, &export, local.sym.to_string());
+        }
+    }
+
     fn visit_call_expr(&mut self, n: &CallExpr) {
         n.visit_children_with(self);
 
-        if let Some((collection, method)) = self.analyze_callee(&n.callee) {
-            let args_to_check: &[usize] = match method.as_str() {
-                "find" | "findOne" | "count" | "countDocuments" | "deleteMany" | "deleteOne"
-                | "aggregate" => &[0],
-                "distinct" => &[1],
-                "updateOne" | "updateMany" | "findOneAndUpdate" | "findOneAndReplace" => &[0],
-                _ => &[],
-            };
+        self.apply_chained_modifier(n);
 
+        if let Some((collection, spec)) = self.analyze_callee(&n.callee) {
             let mut fields = Vec::new();
 
-            for &arg_idx in args_to_check {
-                if let Some(arg) = n.args.get(arg_idx) {
-                    match &*arg.expr {
-                        Expr::Object(obj) => fields.extend(self.extract_fields(obj)),
-                        Expr::Ident(ident) => {
-                            if let Some(obj) = self.local_variables.get(ident.sym.as_str()) {
-                                fields.extend(self.extract_fields(obj));
-                            }
-                        }
-                        Expr::Array(arr) => {
-                            for elem in arr.elems.iter().flatten() {
-                                if let Expr::Object(obj) = &*elem.expr {
-                                    fields.extend(self.extract_fields(obj));
-                                }
-                            }
-                        }
-                        _ => {}
+            match spec.filter {
+                FilterArg::Document(arg_idx) => {
+                    if let Some(arg) = n.args.get(arg_idx) {
+                        fields.extend(self.extract_arg_fields(&arg.expr));
+                    }
+                }
+                FilterArg::Id(arg_idx) => {
+                    if n.args.get(arg_idx).is_some() {
+                        fields.push("_id".to_string());
                     }
                 }
+                FilterArg::None => {}
             }
 
             fields.sort();
             fields.dedup();
 
-            let loc = self.source_map.lookup_char_pos(n.span.lo);
+            let filter_terms = match spec.filter {
+                FilterArg::Document(arg_idx) => n
+                    .args
+                    .get(arg_idx)
+                    .map(|arg| self.extract_predicate(&arg.expr))
+                    .unwrap_or_default(),
+                FilterArg::Id(arg_idx) if n.args.get(arg_idx).is_some() => {
+                    vec!["_id:$eq".to_string()]
+                }
+                _ => Vec::new(),
+            };
+            let pipeline = if spec.name == "aggregate" {
+                n.args
+                    .first()
+                    .map(|arg| self.extract_pipeline_stages(&arg.expr))
+                    .unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+
+            let (start, end) = self.span_bounds(n.span);
 
-            let predicate_arg_idx = if method.as_str() == "distinct" { 1 } else { 0 };
+            let predicate_arg_idx = match spec.filter {
+                FilterArg::Document(arg_idx) | FilterArg::Id(arg_idx) => arg_idx,
+                FilterArg::None => 0,
+            };
 
             let predicate = if let Some(arg) = n.args.get(predicate_arg_idx) {
                 self.source_map
@@ -218,20 +1175,352 @@ impl<'a> Visit for MongoQueryVisitor<'a> {
                 "".to_string()
             };
 
-            let raw_match = format!("{}.{}({})", collection, method, predicate);
+            let written_fields = spec
+                .writes
+                .and_then(|arg_idx| n.args.get(arg_idx))
+                .map(|arg| self.extract_written_fields(&arg.expr))
+                .unwrap_or_default();
+
+            let projection = if spec.name == "aggregate" {
+                n.args
+                    .first()
+                    .and_then(|arg| self.extract_pipeline_projection(&arg.expr))
+            } else {
+                spec.projection
+                    .and_then(|arg_idx| n.args.get(arg_idx))
+                    .and_then(|arg| self.extract_projection(&arg.expr))
+            };
+
+            let mut options = QueryOptions::default();
+            // Driver `find(filter, options)` puts options where Mongoose expects
+            // a projection.
+            if let Some(arg) = spec.projection.and_then(|arg_idx| n.args.get(arg_idx)) {
+                if self
+                    .resolve_object(&arg.expr)
+                    .is_some_and(is_options_object)
+                {
+                    self.extract_options(&arg.expr, &mut options);
+                }
+            }
+            if let Some(arg) = spec.options.and_then(|arg_idx| n.args.get(arg_idx)) {
+                self.extract_options(&arg.expr, &mut options);
+            }
+
+            let mut joins = Vec::new();
+            if spec.name == "aggregate" {
+                if let Some(arg) = n.args.first() {
+                    self.extract_pipeline_joins(&arg.expr, &mut joins);
+                }
+            }
+
+            let raw_match = format!("{}.{}({})", collection, spec.name, predicate);
+            let resolution = self.resolution(spec, &n.args);
 
+            if spec.name == "bulkWrite" {
+                if let Some(arg) = n.args.first() {
+                    self.expand_bulk_write(&collection, start.0, &arg.expr);
+                }
+            }
+
+            self.query_spans.insert(n.span, self.queries.len());
             self.queries.push(MongoQuery {
                 file: self.file_path.clone(),
-                line: loc.line,
+                line: start.0,
+                column: start.1,
+                end_line: end.0,
+                end_column: end.1,
                 collection,
-                method,
+                method: spec.name.to_string(),
+                operation: spec.operation,
                 query_fields: fields,
+                predicate: filter_terms,
+                pipeline,
+                written_fields,
+                projection,
+                options,
                 raw_match,
+                parent: None,
+                class_name: self.class_name.clone(),
+                function_name: self.function_name.clone(),
+                joins,
+                category: FileCategory::Source,
+                language: self.language,
+                resolution,
             });
         }
     }
 }
 
+/// Operation class and the key holding the written document for each
+/// `bulkWrite` operation type.
+fn bulk_operation(name: &str) -> Option<(OperationClass, Option<&'static str>)> {
+    match name {
+        "insertOne" => Some((OperationClass::Insert, Some("document"))),
+        "updateOne" | "updateMany" => Some((OperationClass::Write, Some("update"))),
+        "replaceOne" => Some((OperationClass::Write, Some("replacement"))),
+        "deleteOne" | "deleteMany" => Some((OperationClass::Delete, None)),
+        _ => None,
+    }
+}
+
+/// Update operators whose operand is a `{ path: value }` document.
+const FIELD_UPDATE_OPERATORS: &[&str] = &[
+    "$set",
+    "$setOnInsert",
+    "$unset",
+    "$inc",
+    "$mul",
+    "$min",
+    "$max",
+    "$currentDate",
+    "$rename",
+    "$push",
+    "$addToSet",
+    "$pop",
+    "$pull",
+    "$pullAll",
+    "$bit",
+    // Update pipeline stages.
+    "$addFields",
+];
+
+/// Handles operator updates (`{ $set: { a: 1 } }`), replacement or inserted
+/// documents (`{ a: 1 }`) and update pipeline stages (`{ $unset: ['a'] }`).
+fn collect_written_fields(obj: &ObjectLit, fields: &mut Vec<String>) {
+    for prop in &obj.props {
+        let PropOrSpread::Prop(prop) = prop else {
+            continue;
+        };
+        match &**prop {
+            Prop::KeyValue(kv) => {
+                let Some(key) = get_prop_key(&kv.key) else {
+                    continue;
+                };
+                if !key.starts_with('$') {
+                    fields.push(normalize_update_path(&key));
+                    continue;
+                }
+                if !FIELD_UPDATE_OPERATORS.contains(&key.as_str()) {
+                    continue;
+                }
+                match &*kv.value {
+                    Expr::Object(operand) => {
+                        for (path, value) in object_entries(operand) {
+                            fields.push(normalize_update_path(&path));
+                            // `$rename` also writes the target path.
+                            if key == "$rename" {
+                                if let Some(Expr::Lit(Lit::Str(target))) = value {
+                                    fields.push(normalize_update_path(
+                                        target.value.as_str().unwrap_or_default(),
+                                    ));
+                                }
+                            }
+                        }
+                    }
+                    // Pipeline `$unset: 'a'` / `$unset: ['a', 'b']`.
+                    Expr::Lit(Lit::Str(path)) => fields.push(normalize_update_path(
+                        path.value.as_str().unwrap_or_default(),
+                    )),
+                    Expr::Array(paths) => {
+                        for elem in paths.elems.iter().flatten() {
+                            if let Expr::Lit(Lit::Str(path)) = &*elem.expr {
+                                fields.push(normalize_update_path(
+                                    path.value.as_str().unwrap_or_default(),
+                                ));
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Prop::Shorthand(ident) => fields.push(ident.sym.as_str().to_string()),
+            _ => {}
+        }
+    }
+}
+
+fn object_entries(obj: &ObjectLit) -> Vec<(String, Option<&Expr>)> {
+    obj.props
+        .iter()
+        .filter_map(|prop| {
+            let PropOrSpread::Prop(prop) = prop else {
+                return None;
+            };
+            match &**prop {
+                Prop::KeyValue(kv) => Some((get_prop_key(&kv.key)?, Some(&*kv.value))),
+                Prop::Shorthand(ident) => Some((ident.sym.as_str().to_string(), None)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Appends `field:$operator` terms for a filter document. Operator objects
+/// (`{ age: { $gt: 1, $lt: 9 } }`) give one term per operator; anything else
+/// is an equality match. Logical operators are kept and their branches
+/// flattened in.
+fn predicate_terms(obj: &ObjectLit, terms: &mut Vec<String>) {
+    for (key, value) in object_entries(obj) {
+        if key.starts_with('$') {
+            if let Some(Expr::Array(branches)) = value {
+                for branch in branches.elems.iter().flatten() {
+                    if let Expr::Object(branch) = &*branch.expr {
+                        predicate_terms(branch, terms);
+                    }
+                }
+            }
+            terms.push(key);
+            continue;
+        }
+
+        let operators: Vec<String> = match value {
+            Some(Expr::Object(operand)) => object_entries(operand)
+                .into_iter()
+                .map(|(operator, _)| operator)
+                .collect(),
+            _ => Vec::new(),
+        };
+        if !operators.is_empty() && operators.iter().all(|op| op.starts_with('$')) {
+            for operator in operators {
+                terms.push(format!("{}:{}", key, operator));
+            }
+        } else {
+            terms.push(format!("{}:$eq", key));
+        }
+    }
+}
+
+/// Whether `obj` is an options object rather than a projection: every key is
+/// an option and every literal value has that option's type. `{ comment: 1 }`
+/// projects a field named `comment`, while `{ comment: 'report' }` tags the
+/// query.
+fn is_options_object(obj: &ObjectLit) -> bool {
+    let entries = object_entries(obj);
+    !entries.is_empty()
+        && entries.iter().all(|(key, value)| {
+            OPTION_KEYS.contains(&key.as_str())
+                && value.is_none_or(|value| option_value_fits(key, value))
+        })
+}
+
+/// Whether `value` can be the value of option `key`. Objects, arrays,
+/// identifiers and other computed values fit any option.
+fn option_value_fits(key: &str, value: &Expr) -> bool {
+    let Expr::Lit(lit) = value else {
+        return true;
+    };
+    match key {
+        "limit" | "skip" | "maxTimeMS" | "batchSize" => matches!(lit, Lit::Num(_)),
+        "allowDiskUse" | "lean" | "upsert" | "new" | "ordered" | "bypassDocumentValidation" => {
+            matches!(lit, Lit::Bool(_))
+        }
+        "comment" | "readPreference" | "returnDocument" | "hint" | "sort" | "populate" => {
+            matches!(lit, Lit::Str(_))
+        }
+        _ => false,
+    }
+}
+
+/// Maps `1`, `-1`, `'asc'`, `'desc'`, `'ascending'` and `'descending'` to 1 or -1.
+fn sort_order(expr: &Expr) -> i32 {
+    match expr {
+        Expr::Unary(unary) if unary.op == UnaryOp::Minus => -1,
+        Expr::Lit(Lit::Str(s)) => match s.value.as_str().unwrap_or_default() {
+            "desc" | "descending" | "-1" => -1,
+            _ => 1,
+        },
+        _ => 1,
+    }
+}
+
+/// Parses a Mongoose select string such as `'name email -password'`.
+/// A leading `+` forces inclusion of a field that is deselected by the schema.
+fn parse_select_string(select: &str) -> Projection {
+    let mut projection = Projection::default();
+    for token in select.split_whitespace() {
+        if let Some(field) = token.strip_prefix('-') {
+            projection.exclude.push(field.to_string());
+        } else {
+            projection
+                .include
+                .push(token.trim_start_matches('+').to_string());
+        }
+    }
+    projection.normalize();
+    projection
+}
+
+/// Parses a `{ field: 1 | 0 | true | false | <expression> }` projection.
+/// Expressions such as `$slice` or `$elemMatch` count as inclusions.
+fn parse_projection_object(obj: &ObjectLit) -> Projection {
+    let mut projection = Projection::default();
+    for (field, value) in object_entries(obj) {
+        let excluded = match value {
+            Some(Expr::Lit(Lit::Num(num))) => num.value == 0.0,
+            Some(Expr::Lit(Lit::Bool(b))) => !b.value,
+            _ => false,
+        };
+        if excluded {
+            projection.exclude.push(field);
+        } else {
+            projection.include.push(field);
+        }
+    }
+    projection.normalize();
+    projection
+}
+
+/// Drops positional segments so `items.$.qty`, `items.$[].qty` and
+/// `items.$[elem].qty` are all reported as `items.qty`.
+fn normalize_update_path(path: &str) -> String {
+    path.split('.')
+        .filter(|segment| !segment.starts_with('$'))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn find_prop_value<'b>(obj: &'b ObjectLit, name: &str) -> Option<&'b Expr> {
+    obj.props.iter().find_map(|prop| {
+        let PropOrSpread::Prop(prop) = prop else {
+            return None;
+        };
+        match &**prop {
+            Prop::KeyValue(kv) if get_prop_key(&kv.key).as_deref() == Some(name) => {
+                Some(&*kv.value)
+            }
+            _ => None,
+        }
+    })
+}
+
+/// The `name` argument of a `bulkWrite` operation. Shorthand properties such
+/// as `{ filter, update }` yield the identifier, which the extractors resolve
+/// through local variables like any other argument.
+fn bulk_operation_arg<'b>(body: &'b ObjectLit, name: &str) -> Option<Cow<'b, Expr>> {
+    if let Some(value) = find_prop_value(body, name) {
+        return Some(Cow::Borrowed(value));
+    }
+    body.props.iter().find_map(|prop| match prop {
+        PropOrSpread::Prop(prop) => match &**prop {
+            Prop::Shorthand(ident) if ident.sym == name => {
+                Some(Cow::Owned(Expr::Ident(ident.clone())))
+            }
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+fn string_value(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Lit(Lit::Str(s)) => s.value.as_str().map(str::to_string),
+        Expr::Tpl(tpl) if tpl.exprs.is_empty() => {
+            tpl.quasis.first().map(|quasi| quasi.raw.to_string())
+        }
+        _ => None,
+    }
+}
+
 fn get_prop_key(key: &PropName) -> Option<String> {
     match key {
         PropName::Ident(ident) => Some(ident.sym.as_str().to_string()),
@@ -254,6 +1543,101 @@ fn get_member_prop_name(prop: &MemberProp) -> Option<String> {
     }
 }
 
+/// What the written-document argument of an `operation` is called in
+/// resolution reasons.
+fn written_role(operation: OperationClass) -> &'static str {
+    match operation {
+        OperationClass::Insert => "document",
+        _ => "update",
+    }
+}
+
+/// Keys of `obj` that cannot be read statically.
+fn unread_props(obj: &ObjectLit, role: &str) -> Vec<String> {
+    obj.props
+        .iter()
+        .filter_map(|prop| match prop {
+            PropOrSpread::Spread(_) => Some(format!("{} spreads another object", role)),
+            PropOrSpread::Prop(prop) => match &**prop {
+                Prop::KeyValue(kv) if get_prop_key(&kv.key).is_none() => {
+                    Some(format!("{} has a computed key", role))
+                }
+                _ => None,
+            },
+        })
+        .collect()
+}
+
+/// Why an argument that is neither an object nor an array literal, nor a
+/// local constant holding one, could not be read.
+fn opaque_reason(expr: &Expr) -> String {
+    match expr {
+        Expr::Ident(ident) => format!("`{}` is not a literal declared in this file", ident.sym),
+        Expr::Call(_) | Expr::New(_) => "is built by a function call".to_string(),
+        Expr::Member(_) => "is read from a property".to_string(),
+        Expr::Cond(_) => "is chosen by a conditional".to_string(),
+        _ => "is not a literal".to_string(),
+    }
+}
+
+/// The project module and, for `require('./m').User`, the member read from
+/// it, of a `require('./…')` call.
+fn required_module(expr: &Expr) -> Option<(String, Option<String>)> {
+    match expr {
+        Expr::Member(member) => {
+            let (specifier, None) = required_module(&member.obj)? else {
+                return None;
+            };
+            Some((specifier, Some(get_member_prop_name(&member.prop)?)))
+        }
+        Expr::Call(call) => {
+            let Callee::Expr(callee) = &call.callee else {
+                return None;
+            };
+            if !matches!(&**callee, Expr::Ident(ident) if ident.sym == "require") {
+                return None;
+            }
+            match call.args.first().map(|arg| &*arg.expr) {
+                Some(Expr::Lit(Lit::Str(src))) => src
+                    .value
+                    .as_str()
+                    .filter(|src| src.starts_with('.'))
+                    .map(|src| (src.to_string(), None)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// `(export, local)` pairs bound by `const <pat> = require(…)[.member]`.
+fn required_bindings(pat: &Pat, member: Option<String>) -> Vec<(String, String)> {
+    match pat {
+        Pat::Ident(binding) => vec![(
+            member.unwrap_or_else(|| DEFAULT_EXPORT.to_string()),
+            binding.id.sym.to_string(),
+        )],
+        // Destructuring a single member would need the model's own exports.
+        Pat::Object(object) if member.is_none() => object
+            .props
+            .iter()
+            .filter_map(|prop| match prop {
+                ObjectPatProp::Assign(assign) => {
+                    Some((assign.key.sym.to_string(), assign.key.sym.to_string()))
+                }
+                ObjectPatProp::KeyValue(kv) => match (&kv.key, &*kv.value) {
+                    (PropName::Ident(key), Pat::Ident(local)) => {
+                        Some((key.sym.to_string(), local.id.sym.to_string()))
+                    }
+                    _ => None,
+                },
+                ObjectPatProp::Rest(_) => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
 fn get_injected_model_name(decorator: &Decorator) -> Option<String> {
     let Expr::Call(call) = &*decorator.expr else {
         return None;
@@ -282,36 +1666,685 @@ fn get_injected_model_name(decorator: &Decorator) -> Option<String> {
     }
 }
 
+/// A syntax error found while parsing a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParseError {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    /// The parser gave up, so nothing in the file is analyzed. Otherwise it
+    /// recovered and the file's queries were still extracted.
+    pub fatal: bool,
+}
+
+/// Queries and suppression comments found in one source file.
+#[derive(Debug, Clone, Default)]
+pub struct ParsedFile {
+    pub queries: Vec<MongoQuery>,
+    pub suppressions: Vec<Suppression>,
+    pub parse_errors: Vec<ParseError>,
+    /// Other files read to analyze this one, with their content hashes.
+    pub dependencies: BTreeMap<String, String>,
+}
+
+impl ParsedFile {
+    /// Tags every query with the category of the file it came from.
+    pub fn set_category(&mut self, category: FileCategory) {
+        for query in &mut self.queries {
+            query.category = category;
+        }
+    }
+
+    /// Attributes every query, suppression and parse error to `file`.
+    pub fn set_file(&mut self, file: &str) {
+        for query in &mut self.queries {
+            query.file = file.to_string();
+        }
+        for suppression in &mut self.suppressions {
+            suppression.file = file.to_string();
+        }
+        for error in &mut self.parse_errors {
+            error.file = file.to_string();
+        }
+    }
+}
+
 pub fn parse_file(content: &str, file_path: &str) -> Vec<MongoQuery> {
-    let cm: Lrc<SourceMap> = Default::default();
-    let fm = cm.new_source_file(
-        FileName::Custom(file_path.to_string()).into(),
-        content.to_string(),
-    );
+    parse_source(content, file_path).queries
+}
 
-    let lexer = Lexer::new(
-        Syntax::Typescript(TsSyntax {
+/// Parses `fm` with the syntax its extension calls for, returning the
+/// program, or the fatal error, and the errors the parser recovered from.
+pub(crate) fn parse_program(
+    fm: &SourceFile,
+    file_path: &str,
+    comments: Option<&dyn Comments>,
+) -> (PResult<Program>, Vec<swc_ecma_parser::error::Error>) {
+    let extension = file_path
+        .rsplit_once('.')
+        .map_or("", |(_, extension)| extension);
+    let syntax = match Language::of(file_path).unwrap_or_default() {
+        Language::TypeScript => Syntax::Typescript(TsSyntax {
+            tsx: extension == "tsx",
             decorators: true,
             ..Default::default()
         }),
-        Default::default(),
-        StringInput::from(&*fm),
-        None,
-    );
+        // JSX is common in plain `.js` React code too.
+        Language::JavaScript => Syntax::Es(EsSyntax {
+            jsx: matches!(extension, "js" | "jsx"),
+            decorators: true,
+            allow_return_outside_function: extension == "cjs",
+            ..Default::default()
+        }),
+    };
+    let lexer = Lexer::new(syntax, Default::default(), StringInput::from(fm), comments);
 
     let mut parser = Parser::new_from(lexer);
 
-    let module = parser.parse_module().map_or_else(
-        |_| Module {
+    // CommonJS files are scripts: `require` instead of `import`, and neither
+    // strict mode nor top-level `await`. Plain `.js` may be either, so it is a
+    // module only if it imports or exports; otherwise sloppy-mode code such
+    // as `with` would be reported as a syntax error.
+    let program = match extension {
+        "cjs" => parser.parse_script().map(Program::Script),
+        "js" | "jsx" => parser.parse_program(),
+        _ => parser.parse_module().map(Program::Module),
+    };
+    (program, parser.take_errors())
+}
+
+pub fn parse_source(content: &str, file_path: &str) -> ParsedFile {
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(
+        FileName::Custom(file_path.to_string()).into(),
+        content.to_string(),
+    );
+    let comments = SingleThreadedComments::default();
+    let (program, recovered) = parse_program(&fm, file_path, Some(&comments));
+
+    let to_parse_error = |err: swc_ecma_parser::error::Error, fatal: bool| {
+        let start = cm.lookup_char_pos(err.span().lo);
+        ParseError {
+            file: file_path.to_string(),
+            line: start.line,
+            column: start.col.0 + 1,
+            message: err.kind().msg().into_owned(),
+            fatal,
+        }
+    };
+    // Errors the parser recovered from still leave a usable tree; only a
+    // fatal one empties the file.
+    let mut parse_errors: Vec<ParseError> = recovered
+        .into_iter()
+        .map(|err| to_parse_error(err, false))
+        .collect();
+    let program = program.unwrap_or_else(|err| {
+        parse_errors.push(to_parse_error(err, true));
+        Program::Module(Module {
             span: Default::default(),
             body: Vec::new(),
             shebang: None,
-        },
-        |m| m,
-    );
+        })
+    });
 
+    let mut injects = InjectsModels(false);
+    program.visit_with(&mut injects);
     let mut visitor = MongoQueryVisitor::new(&cm, file_path.to_string());
-    module.visit_with(&mut visitor);
+    visitor.resolve_local_models = !injects.0;
+    program.visit_with(&mut visitor);
+
+    let (leading, trailing) = comments.take_all();
+    let all_comments: Vec<Comment> = leading
+        .borrow()
+        .values()
+        .chain(trailing.borrow().values())
+        .flatten()
+        .cloned()
+        .collect();
 
-    visitor.queries
+    ParsedFile {
+        queries: visitor.queries,
+        suppressions: suppression::from_comments(&all_comments, &cm, file_path),
+        parse_errors,
+        dependencies: visitor.dependencies,
+    }
+}
+
+/// Finds `@InjectModel` decorators.
+struct InjectsModels(bool);
+
+impl Visit for InjectsModels {
+    fn visit_decorator(&mut self, n: &Decorator) {
+        self.0 |= get_injected_model_name(n).is_some();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{parse_file, parse_source};
+    use crate::{Language, OperationClass, ResolutionStatus, TestDir};
+
+    #[test]
+    fn recognizes_mongoose_id_methods_and_inserts() {
+        let source = r#"
+            class UserService {
+                async run(id: string) {
+                    await this.userModel.findById(id);
+                    await this.userModel.findByIdAndUpdate(id, { $set: { name: 'a' } });
+                    await this.userModel.create({ name: 'a' });
+                    await this.userModel.estimatedDocumentCount();
+                }
+            }
+        "#;
+
+        let queries = parse_file(source, "user.service.ts");
+        let summary: Vec<_> = queries
+            .iter()
+            .map(|q| (q.method.as_str(), q.operation, q.query_fields.clone()))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("findById", OperationClass::Read, vec!["_id".to_string()]),
+                (
+                    "findByIdAndUpdate",
+                    OperationClass::Write,
+                    vec!["_id".to_string()]
+                ),
+                ("create", OperationClass::Insert, vec![]),
+                ("estimatedDocumentCount", OperationClass::Read, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn array_filters_are_not_reported_as_predicate_fields() {
+        let source = r#"
+            db.collection('orders').updateMany(
+                { status: 'open' },
+                { $set: { 'items.$[item].shipped': true } },
+                { arrayFilters: [{ 'item.sku': 'A1' }] }
+            );
+        "#;
+
+        let queries = parse_file(source, "orders.ts");
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0].operation, OperationClass::Write);
+        assert_eq!(queries[0].query_fields, vec!["status".to_string()]);
+    }
+
+    #[test]
+    fn expands_bulk_write_operations_from_variables() {
+        let source = r#"
+            const ops = [
+                { updateOne: { filter: { organizationId, sku }, update: { $inc: { qty: 1 } }, upsert: true } },
+                { deleteMany: { filter: { expired: true } } },
+                { insertOne: { document: { sku } } },
+            ];
+            db.collection('inventory').bulkWrite(ops);
+        "#;
+
+        let queries = parse_file(source, "inventory.ts");
+        let summary: Vec<_> = queries
+            .iter()
+            .map(|q| {
+                (
+                    q.method.as_str(),
+                    q.operation,
+                    q.query_fields.join(","),
+                    q.parent
+                        .as_ref()
+                        .map(|p| (p.method.as_str(), p.line, p.index)),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "updateOne",
+                    OperationClass::Write,
+                    "organizationId,sku".to_string(),
+                    Some(("bulkWrite", 7, 0))
+                ),
+                (
+                    "deleteMany",
+                    OperationClass::Delete,
+                    "expired".to_string(),
+                    Some(("bulkWrite", 7, 1))
+                ),
+                (
+                    "insertOne",
+                    OperationClass::Insert,
+                    String::new(),
+                    Some(("bulkWrite", 7, 2))
+                ),
+                ("bulkWrite", OperationClass::Write, String::new(), None),
+            ]
+        );
+        assert_eq!(queries[0].written_fields, vec!["qty".to_string()]);
+        assert_eq!(queries[2].written_fields, vec!["sku".to_string()]);
+    }
+
+    #[test]
+    fn resolves_shorthand_bulk_write_arguments() {
+        let source = r#"
+            const filter = { organizationId, sku };
+            const update = { $inc: { qty: 1 } };
+            db.collection('inventory').bulkWrite([{ updateOne: { filter, update, upsert } }]);
+        "#;
+
+        let queries = parse_file(source, "inventory.ts");
+        assert_eq!(queries[0].method, "updateOne");
+        assert_eq!(queries[0].query_fields, vec!["organizationId", "sku"]);
+        assert_eq!(queries[0].written_fields, vec!["qty"]);
+    }
+
+    #[test]
+    fn resolves_bulk_write_operations_individually() {
+        let source = r#"
+            db.collection('inventory').bulkWrite(buildOps());
+            db.collection('inventory').bulkWrite([
+                { updateOne: { filter: filterFor(sku), update: { $inc: { qty: 1 } } } },
+                { deleteOne: { filter: { sku } } },
+            ]);
+        "#;
+
+        let queries = parse_file(source, "inventory.ts");
+        let statuses: Vec<(&str, ResolutionStatus)> = queries
+            .iter()
+            .map(|q| (q.method.as_str(), q.resolution.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("bulkWrite", ResolutionStatus::Unresolved),
+                ("updateOne", ResolutionStatus::Unresolved),
+                ("deleteOne", ResolutionStatus::Resolved),
+                ("bulkWrite", ResolutionStatus::Resolved),
+            ]
+        );
+        assert_eq!(
+            queries[0].resolution.reasons,
+            vec!["operations is built by a function call"]
+        );
+        assert_eq!(
+            queries[1].resolution.reasons,
+            vec!["filter is built by a function call"]
+        );
+    }
+
+    #[test]
+    fn extracts_written_paths_from_update_documents() {
+        let source = r#"
+            db.collection('users').updateOne(
+                { _id: id },
+                {
+                    $set: { 'profile.name': name, 'roles.$[role].active': true },
+                    $inc: { loginCount: 1 },
+                    $push: { 'sessions.$.events': event },
+                    $unset: { legacy: '' },
+                    $setOnInsert: { createdAt },
+                }
+            );
+            db.collection('users').updateMany({}, [{ $set: { flag: true } }, { $unset: ['tmp'] }]);
+            db.collection('users').replaceOne({ _id: id }, { email, status: 'active' });
+        "#;
+
+        let queries = parse_file(source, "users.ts");
+        let written: Vec<_> = queries.iter().map(|q| q.written_fields.join(",")).collect();
+
+        assert_eq!(
+            written,
+            vec![
+                "createdAt,legacy,loginCount,profile.name,roles.active,sessions.events",
+                "flag,tmp",
+                "email,status",
+            ]
+        );
+    }
+
+    #[test]
+    fn extracts_projections_from_arguments_select_and_pipelines() {
+        let source = r#"
+            class UserService {
+                async run() {
+                    await this.userModel.find({ active: true }, 'name email -password').lean();
+                    await this.userModel.findOne({ email }).select({ name: 1, _id: 0 }).exec();
+                    await db.collection('users').find({ active: true }, { projection: { name: 1 }, limit: 5 });
+                    await db.collection('users').find({ active: true }, { sort: { name: 1 } });
+                    await this.userModel.aggregate([{ $match: { active: true } }, { $project: { name: 1 } }]);
+                    await this.userModel.find({ active: true }).select('');
+                    await this.userModel.find({ active: true }, '   ');
+                    await db.collection('users').find({ active: true }, { projection: {} });
+                    await db.collection('comments').find({ postId }, { comment: 1 });
+                    await db.collection('comments').find({ postId }, { comment: 'audit', limit: 1 });
+                }
+            }
+        "#;
+
+        let queries = parse_file(source, "user.service.ts");
+        let projections: Vec<_> = queries
+            .iter()
+            .map(|q| {
+                q.projection
+                    .as_ref()
+                    .map(|p| (p.include.join(","), p.exclude.join(",")))
+            })
+            .collect();
+
+        assert_eq!(
+            projections,
+            vec![
+                Some(("email,name".to_string(), "password".to_string())),
+                Some(("name".to_string(), "_id".to_string())),
+                Some(("name".to_string(), String::new())),
+                None,
+                Some(("name".to_string(), String::new())),
+                None,
+                None,
+                None,
+                Some(("comment".to_string(), String::new())),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn options_argument_agrees_with_chained_modifiers() {
+        let source = r#"
+            class OrderService {
+                async run(session) {
+                    await db.collection('orders').find(
+                        { status },
+                        { sort: { createdAt: -1 }, limit: 20, skip: 40, hint: 'status_1', session, readPreference: 'secondary', maxTimeMS: 500 }
+                    );
+                    await this.orderModel.find({ status })
+                        .sort('-createdAt')
+                        .limit(20)
+                        .skip(40)
+                        .hint('status_1')
+                        .session(session)
+                        .read('secondary')
+                        .maxTimeMS(500)
+                        .exec();
+                }
+            }
+        "#;
+
+        let queries = parse_file(source, "order.service.ts");
+        assert_eq!(queries.len(), 2);
+        assert_eq!(queries[0].options, queries[1].options);
+        assert_eq!(queries[0].options.sort[0].field, "createdAt");
+        assert_eq!(queries[0].options.sort[0].order, -1);
+        assert_eq!(queries[0].options.limit.as_deref(), Some("20"));
+        assert_eq!(
+            queries[0].options.read_preference.as_deref(),
+            Some("secondary")
+        );
+        assert!(queries[0].options.session);
+        assert!(queries[0].projection.is_none());
+    }
+
+    #[test]
+    fn reads_update_options() {
+        let source = r#"
+            db.collection('orders').updateOne(
+                { _id: id },
+                { $set: { 'items.$[item].shipped': true } },
+                { upsert: true, arrayFilters: [{ 'item.sku': sku }], collation: { locale: 'en' } }
+            );
+        "#;
+
+        let queries = parse_file(source, "orders.ts");
+        let options = &queries[0].options;
+        assert!(options.upsert);
+        assert_eq!(options.array_filters, vec!["item".to_string()]);
+        assert_eq!(options.collation.as_deref(), Some("en"));
+    }
+
+    #[test]
+    fn names_default_exported_and_assigned_classes() {
+        let source = r#"
+            export default class PostService {
+                list() { return db.collection('posts').find({ published: true }); }
+            }
+            const CommentService = class {
+                list() { return db.collection('comments').find({ postId }); }
+            };
+            const Audit = class AuditLog {
+                list() { return db.collection('audit').find({ at }); }
+            };
+        "#;
+
+        let classes: Vec<Option<String>> = parse_file(source, "posts.ts")
+            .into_iter()
+            .map(|query| query.class_name)
+            .collect();
+        assert_eq!(
+            classes,
+            vec![
+                Some("PostService".to_string()),
+                Some("CommentService".to_string()),
+                Some("AuditLog".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn records_enclosing_scope_and_joins() {
+        let source = r#"
+            export class OrderService {
+                async summary() {
+                    return db.collection('orders').aggregate([
+                        { $lookup: { from: 'users', localField: 'userId', foreignField: '_id', as: 'user' } },
+                        { $unionWith: 'archivedOrders' },
+                    ]);
+                }
+            }
+
+            const loadPosts = async () => this.postModel.find({ published: true }).populate({ path: 'author', model: 'User' });
+            const loadComments = async () => this.commentModel.find({}).populate('author post');
+        "#;
+
+        let queries = parse_file(source, "orders.ts");
+        assert_eq!(queries[0].class_name.as_deref(), Some("OrderService"));
+        assert_eq!(queries[0].function_name.as_deref(), Some("summary"));
+        let joined: Vec<(&str, &str)> = queries[0]
+            .joins
+            .iter()
+            .map(|join| (join.from.as_deref().unwrap_or_default(), join.via.as_str()))
+            .collect();
+        assert_eq!(
+            joined,
+            vec![("users", "$lookup"), ("archivedOrders", "$unionWith")]
+        );
+
+        assert_eq!(queries[1].class_name, None);
+        assert_eq!(queries[1].function_name.as_deref(), Some("loadPosts"));
+        assert_eq!(queries[1].joins[0].from.as_deref(), Some("User"));
+        assert_eq!(queries[1].joins[0].path.as_deref(), Some("author"));
+        assert_eq!(queries[1].joins[0].via, "populate");
+        let populated: Vec<(Option<&str>, Option<&str>)> = queries[2]
+            .joins
+            .iter()
+            .map(|join| (join.from.as_deref(), join.path.as_deref()))
+            .collect();
+        assert_eq!(
+            populated,
+            vec![(None, Some("author")), (None, Some("post"))]
+        );
+    }
+
+    #[test]
+    fn parses_javascript_and_resolves_required_models() {
+        let dir = TestDir::new("required-models");
+        dir.write(
+            "models/user.js",
+            "const mongoose = require('mongoose');\n\
+             module.exports = mongoose.model('User', new mongoose.Schema({}));",
+        );
+        dir.write(
+            "scripts/models.js",
+            "const Order = model('Order', orderSchema);\nmodule.exports = { orderModel: Order };",
+        );
+        let script = dir.write(
+            "scripts/migrate.cjs",
+            r#"
+            const User = require('../models/user');
+            const { orderModel } = require('./models');
+            const mongoose = require('mongoose');
+            const Audit = mongoose.model('Audit', auditSchema);
+
+            async function migrate() {
+                await User.updateMany({ legacy: true }, { $set: { legacy: false } });
+                await orderModel.find({ status: 'open' });
+                await Audit.insertOne({ at: new Date() });
+            }
+            "#,
+        );
+
+        let parsed = parse_source(
+            &fs::read_to_string(&script).unwrap(),
+            &script.to_string_lossy(),
+        );
+        let collections: Vec<&str> = parsed
+            .queries
+            .iter()
+            .map(|q| q.collection.as_str())
+            .collect();
+        assert_eq!(collections, vec!["User", "Order", "Audit"]);
+        assert!(parsed
+            .queries
+            .iter()
+            .all(|q| q.language == Language::JavaScript));
+        assert_eq!(parsed.dependencies.len(), 2);
+
+        dir.write(
+            "src/post.model.ts",
+            "export default model<IPost>('Post', PostSchema);",
+        );
+        let component = dir.write(
+            "src/Posts.jsx",
+            r#"
+            import Post from './post.model';
+            export const Posts = () => <List items={Post.find({ published: true })} />;
+            "#,
+        );
+        let queries = parse_file(
+            &fs::read_to_string(&component).unwrap(),
+            &component.to_string_lossy(),
+        );
+        assert_eq!(queries[0].collection, "Post");
+        assert_eq!(queries[0].query_fields, vec!["published"]);
+    }
+
+    #[test]
+    fn ignores_imports_that_are_not_models() {
+        let dir = TestDir::new("non-model-imports");
+        dir.write(
+            "src/constants.ts",
+            "export const ROLES = { admin: 'admin' };",
+        );
+        dir.write("src/logger.ts", "export class Logger { static count() {} }");
+        dir.write(
+            "src/user.service.ts",
+            "export class UserService { static create(dto) {} }",
+        );
+        dir.write(
+            "src/user.model.ts",
+            "export const User = model('User', UserSchema);",
+        );
+        let source = r#"
+            import { ROLES } from './constants';
+            import { Logger } from './logger';
+            import { UserService } from './user.service';
+            import { User } from './user.model';
+
+            export async function seed() {
+                ROLES.find((role) => role === 'admin');
+                Logger.count();
+                UserService.create({ email });
+                await User.find({ email });
+            }
+        "#;
+        let path = dir.write("src/seed.ts", source);
+        let queries = parse_file(source, &path.to_string_lossy());
+        let collections: Vec<&str> = queries.iter().map(|q| q.collection.as_str()).collect();
+        assert_eq!(collections, vec!["User"]);
+
+        let injected = r#"
+            import { User } from './user.model';
+
+            export class UserRepository {
+                constructor(@InjectModel(User.name) private userModel: Model<User>) {}
+
+                find() {
+                    return User.find({ email });
+                }
+            }
+        "#;
+        let path = dir.write("src/user.repository.ts", injected);
+        assert!(parse_file(injected, &path.to_string_lossy()).is_empty());
+    }
+
+    #[test]
+    fn records_resolution_status_and_parse_errors() {
+        let source = r#"
+            async function run(filter, tenantId) {
+                const active = { status: 'active' };
+                await db.collection('users').find(active);
+                await db.collection('users').find({ ...filter, tenantId });
+                await db.collection('users').updateOne({ tenantId }, buildUpdate());
+                await db.collection('users').find(filter);
+            }
+        "#;
+        let queries = parse_file(source, "users.js");
+        let statuses: Vec<ResolutionStatus> = queries.iter().map(|q| q.resolution.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ResolutionStatus::Resolved,
+                ResolutionStatus::Partial,
+                ResolutionStatus::Partial,
+                ResolutionStatus::Unresolved,
+            ]
+        );
+        assert_eq!(
+            queries[1].resolution.reasons,
+            vec!["filter spreads another object"]
+        );
+        assert_eq!(
+            queries[2].resolution.reasons,
+            vec!["update is built by a function call"]
+        );
+        assert_eq!(
+            queries[3].resolution.reasons,
+            vec!["filter `filter` is not a literal declared in this file"]
+        );
+
+        let parsed = parse_source("const x = ;\ndb.collection('users').find({});", "broken.ts");
+        assert!(parsed.queries.is_empty());
+        assert_eq!(parsed.parse_errors.len(), 1);
+        assert!(parsed.parse_errors[0].fatal);
+        assert_eq!(
+            (parsed.parse_errors[0].line, parsed.parse_errors[0].column),
+            (1, 11)
+        );
+
+        let parsed = parse_source(
+            "db.collection('users').find({});\nclass A { constructor() {} constructor() {} }",
+            "recovered.mjs",
+        );
+        assert_eq!(parsed.queries.len(), 1);
+        assert_eq!(parsed.parse_errors.len(), 1);
+        assert!(!parsed.parse_errors[0].fatal);
+        assert_eq!(parsed.parse_errors[0].line, 2);
+    }
 }
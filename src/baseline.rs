@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ConfigWarning, Rule};
+
+pub const BASELINE_FILE_NAME: &str = "redshift.baseline.json";
+
+/// Version of the baseline file format.
+pub const BASELINE_VERSION: u32 = 1;
+
+/// 64-bit FNV-1a over `parts`, as 16 hex digits. Parts are separated by a NUL
+/// byte so `["ab", "c"]` and `["a", "bc"]` differ.
+pub fn fingerprint(parts: &[&str]) -> String {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    let hash = parts.join("\0").bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    });
+    format!("{:016x}", hash)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BaselineEntry {
+    pub fingerprint: String,
+    pub rule: Rule,
+    pub collection: Option<String>,
+    /// Message of the first matching finding, for humans reviewing the file.
+    pub message: String,
+    /// How many findings with this fingerprint are accepted.
+    pub count: usize,
+}
+
+/// Recorded findings that `analyze --baseline` does not report again.
+///
+/// Entries are matched by [`ConfigWarning::fingerprint`], which ignores file
+/// names and line numbers, so moving or reformatting code keeps legacy
+/// findings suppressed. Identical findings share a fingerprint; the entry's
+/// count says how many of them are accepted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Baseline {
+    pub version: u32,
+    /// Ordered by fingerprint, so rewriting an unchanged baseline is a no-op.
+    pub entries: Vec<BaselineEntry>,
+}
+
+/// What applying a baseline did, reported alongside the remaining findings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BaselineSummary {
+    pub path: String,
+    /// Findings hidden because the baseline accepts them.
+    pub suppressed: usize,
+    /// Entries, with the number of findings that disappeared, that no longer
+    /// match anything and can be dropped by rewriting the baseline.
+    pub fixed: Vec<BaselineEntry>,
+}
+
+impl Baseline {
+    pub fn from_warnings(warnings: &[ConfigWarning]) -> Self {
+        let mut entries: BTreeMap<&str, BaselineEntry> = BTreeMap::new();
+        for warning in warnings {
+            entries
+                .entry(&warning.fingerprint)
+                .and_modify(|entry| entry.count += 1)
+                .or_insert_with(|| BaselineEntry {
+                    fingerprint: warning.fingerprint.clone(),
+                    rule: warning.rule,
+                    collection: warning.collection.clone(),
+                    message: warning.message.clone(),
+                    count: 1,
+                });
+        }
+
+        Self {
+            version: BASELINE_VERSION,
+            entries: entries.into_values().collect(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path).map_err(|err| {
+            std::io::Error::new(
+                err.kind(),
+                format!("Failed to read baseline {}: {}", path.display(), err),
+            )
+        })?;
+        let baseline: Self = serde_json::from_str(&content).map_err(|err| {
+            std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Failed to parse baseline {}: {}", path.display(), err),
+            )
+        })?;
+        if baseline.version != BASELINE_VERSION {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Unsupported baseline version {} in {}",
+                    baseline.version,
+                    path.display()
+                ),
+            )
+            .into());
+        }
+        Ok(baseline)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut content = serde_json::to_string_pretty(self)?;
+        content.push('\n');
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Splits `warnings` into those the baseline does not accept, and a
+    /// summary of what it suppressed and which entries are now fixed.
+    pub fn apply(
+        &self,
+        path: &Path,
+        warnings: Vec<ConfigWarning>,
+    ) -> (Vec<ConfigWarning>, BaselineSummary) {
+        let mut remaining: BTreeMap<&str, usize> = self
+            .entries
+            .iter()
+            .map(|entry| (entry.fingerprint.as_str(), entry.count))
+            .collect();
+
+        let mut suppressed = 0;
+        let mut new = Vec::new();
+        for warning in warnings {
+            match remaining.get_mut(warning.fingerprint.as_str()) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    suppressed += 1;
+                }
+                _ => new.push(warning),
+            }
+        }
+
+        let fixed = self
+            .entries
+            .iter()
+            .filter_map(|entry| {
+                let count = remaining[entry.fingerprint.as_str()];
+                (count > 0).then(|| BaselineEntry {
+                    count,
+                    ..entry.clone()
+                })
+            })
+            .collect();
+
+        (
+            new,
+            BaselineSummary {
+                path: path.display().to_string(),
+                suppressed,
+                fixed,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::Baseline;
+    use crate::config::{ConfigWarning, Rule, Severity};
+    use crate::MongoQuery;
+
+    fn warning(line: usize, fields: &[&str]) -> ConfigWarning {
+        let query = MongoQuery {
+            file: "src/user.service.ts".to_string(),
+            line,
+            collection: "users".to_string(),
+            method: "find".to_string(),
+            query_fields: fields.iter().map(|field| field.to_string()).collect(),
+            class_name: Some("UserService".to_string()),
+            function_name: Some("findActive".to_string()),
+            ..Default::default()
+        };
+        ConfigWarning::at_query(
+            Rule::RecommendedPredicateMiss,
+            Severity::Warning,
+            "missing tenant".to_string(),
+            &query,
+        )
+    }
+
+    #[test]
+    fn fingerprints_survive_line_moves_and_fixed_entries_are_reported() {
+        let baseline = Baseline::from_warnings(&[warning(10, &["email"]), warning(20, &["role"])]);
+        assert_eq!(baseline.entries.len(), 2);
+
+        // The email query moved; the role query was fixed; a new query appeared.
+        let (new, summary) = baseline.apply(
+            Path::new("redshift.baseline.json"),
+            vec![warning(42, &["email"]), warning(50, &["isActive"])],
+        );
+
+        assert_eq!(new.len(), 1);
+        assert_eq!(new[0].line, Some(50));
+        assert_eq!(summary.suppressed, 1);
+        assert_eq!(summary.fixed.len(), 1);
+        assert_eq!(
+            summary.fixed[0].fingerprint,
+            warning(20, &["role"]).fingerprint
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast_parser::{ParseError, ParsedFile};
+use crate::baseline::fingerprint;
+use crate::models::{resolve_base, ABSENT_MODULE};
+use crate::suppression::Suppression;
+use crate::{relative_path, MongoQuery};
+
+/// Directory, under the analyzed root, that holds the cache.
+pub const CACHE_DIR_NAME: &str = ".redshift-cache";
+
+const CACHE_FILE_NAME: &str = "analysis.json";
+
+/// Version of the cache file format. A cache written by another format or
+/// tool version is discarded, since extraction may have changed.
+const CACHE_VERSION: u32 = 6;
+
+/// Hash of a source file's content.
+pub fn content_hash(content: &str) -> String {
+    fingerprint(&[content])
+}
+
+/// What was extracted from one file, and what it was extracted from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedFile {
+    pub hash: String,
+    /// Other files read while extracting this one, with their content hashes
+    /// at the time, so a change to any of them invalidates the entry.
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, String>,
+    pub queries: Vec<MongoQuery>,
+    pub suppressions: Vec<Suppression>,
+    #[serde(default)]
+    pub parse_errors: Vec<ParseError>,
+}
+
+/// Per-file extraction results from an earlier scan, stored in
+/// `.redshift-cache/analysis.json` under the analyzed root and keyed by path
+/// relative to it, so the cache stays valid when the project is moved or
+/// scanned from another directory.
+///
+/// Only extraction is cached. Rules, baselines and suppressions are applied
+/// to the combined results on every run, so editing `redshift.config.json`
+/// never needs to invalidate anything.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisCache {
+    version: u32,
+    tool_version: String,
+    files: BTreeMap<String, CachedFile>,
+    #[serde(skip)]
+    root: PathBuf,
+    /// Current hashes of dependencies checked so far, so a module imported
+    /// by many files is read once per scan. `None` if it cannot be read.
+    #[serde(skip)]
+    dependency_hashes: Mutex<HashMap<PathBuf, Option<String>>>,
+}
+
+impl AnalysisCache {
+    /// An empty cache for the project at `root`.
+    pub fn new(root: &Path) -> Self {
+        Self {
+            version: CACHE_VERSION,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            files: BTreeMap::new(),
+            root: root.to_path_buf(),
+            dependency_hashes: Mutex::default(),
+        }
+    }
+
+    /// Reads the cache of the project at `root`. A missing, unreadable or
+    /// outdated cache is treated as empty rather than as an error.
+    pub fn load(root: &Path) -> Self {
+        let fresh = Self::new(root);
+        fs::read_to_string(fresh.dir().join(CACHE_FILE_NAME))
+            .ok()
+            .and_then(|content| serde_json::from_str::<Self>(&content).ok())
+            .filter(|cache| {
+                cache.version == fresh.version && cache.tool_version == fresh.tool_version
+            })
+            .map(|cache| Self {
+                root: fresh.root.clone(),
+                ..cache
+            })
+            .unwrap_or(fresh)
+    }
+
+    /// The directory the cache is written to.
+    pub fn dir(&self) -> PathBuf {
+        self.root.join(CACHE_DIR_NAME)
+    }
+
+    /// Results for `path`, if they were extracted from content with `hash`
+    /// and none of their dependencies changed since.
+    pub fn get(&self, path: &str, hash: &str) -> Option<ParsedFile> {
+        let entry = self.files.get(&relative_path(&self.root, path))?;
+        let fresh = entry.hash == hash
+            && entry.dependencies.iter().all(|(dependency, hash)| {
+                let dependency = self.root.join(dependency);
+                if hash == ABSENT_MODULE {
+                    resolve_base(&dependency).is_none()
+                } else {
+                    self.dependency_hash(&dependency).as_ref() == Some(hash)
+                }
+            });
+        if !fresh {
+            return None;
+        }
+
+        let mut parsed = ParsedFile {
+            queries: entry.queries.clone(),
+            suppressions: entry.suppressions.clone(),
+            parse_errors: entry.parse_errors.clone(),
+            dependencies: entry
+                .dependencies
+                .iter()
+                .map(|(dependency, hash)| {
+                    let dependency = self.root.join(dependency);
+                    (dependency.to_string_lossy().into_owned(), hash.clone())
+                })
+                .collect(),
+        };
+        parsed.set_file(path);
+        Some(parsed)
+    }
+
+    /// The content hash of `path` as of this scan.
+    fn dependency_hash(&self, path: &Path) -> Option<String> {
+        let hashes = || {
+            self.dependency_hashes
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+        };
+        if let Some(hash) = hashes().get(path) {
+            return hash.clone();
+        }
+        let hash = fs::read_to_string(path)
+            .ok()
+            .map(|content| content_hash(&content));
+        hashes().insert(path.to_path_buf(), hash.clone());
+        hash
+    }
+
+    pub fn insert(&mut self, path: &str, hash: String, parsed: &ParsedFile) {
+        let relative = relative_path(&self.root, path);
+        let mut parsed = parsed.clone();
+        parsed.set_file(&relative);
+        self.files.insert(
+            relative,
+            CachedFile {
+                hash,
+                dependencies: parsed
+                    .dependencies
+                    .iter()
+                    .map(|(dependency, hash)| (relative_path(&self.root, dependency), hash.clone()))
+                    .collect(),
+                queries: parsed.queries,
+                suppressions: parsed.suppressions,
+                parse_errors: parsed.parse_errors,
+            },
+        );
+    }
+
+    /// Replaces the cache on disk, creating its directory with a
+    /// `.gitignore` so it is never committed.
+    pub fn write(&self) -> io::Result<()> {
+        let dir = self.dir();
+        fs::create_dir_all(&dir)?;
+        let gitignore = dir.join(".gitignore");
+        if !gitignore.exists() {
+            fs::write(gitignore, "*\n")?;
+        }
+
+        // Write then rename, so an interrupted run leaves the old cache intact.
+        let temporary = dir.join(format!("{}.tmp", CACHE_FILE_NAME));
+        fs::write(&temporary, serde_json::to_vec(self)?)?;
+        fs::rename(temporary, dir.join(CACHE_FILE_NAME))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{content_hash, AnalysisCache, CACHE_DIR_NAME};
+    use crate::ast_parser::parse_source;
+    use crate::TestDir;
+
+    #[test]
+    fn reuses_results_until_content_changes() {
+        let dir = TestDir::new("cache");
+        let source = "db.collection('users').find({ email });";
+        let file = dir.write("src/users.ts", source);
+        let file = file.to_string_lossy();
+
+        let mut cache = AnalysisCache::new(dir.path());
+        cache.insert(&file, content_hash(source), &parse_source(source, &file));
+        cache.write().unwrap();
+
+        let cache = AnalysisCache::load(dir.path());
+        let hit = cache.get(&file, &content_hash(source)).unwrap();
+        assert_eq!(hit.queries[0].query_fields, vec!["email"]);
+        assert_eq!(hit.queries[0].file, file);
+        assert!(cache
+            .get(&file, &content_hash("db.collection('users').find({});"))
+            .is_none());
+        assert!(dir.path().join(CACHE_DIR_NAME).join(".gitignore").exists());
+    }
+
+    #[test]
+    fn survives_moving_the_project_and_tracks_model_dependencies() {
+        let dir = TestDir::new("cache-moved");
+        let model = "export const User = model('User', schema);";
+        let source = "import { User } from './user';\nUser.find({ email });";
+        dir.write("old/user.ts", model);
+        let file = dir.write("old/users.ts", source);
+        let file = file.to_string_lossy();
+
+        let mut cache = AnalysisCache::new(&dir.path().join("old"));
+        let parsed = parse_source(source, &file);
+        assert_eq!(parsed.queries[0].collection, "User");
+        cache.insert(&file, content_hash(source), &parsed);
+        cache.write().unwrap();
+
+        let moved = dir.path().join("new");
+        fs::rename(dir.path().join("old"), &moved).unwrap();
+        let moved_file = moved.join("users.ts");
+        let moved_file = moved_file.to_string_lossy();
+        let cache = AnalysisCache::load(&moved);
+        let hit = cache.get(&moved_file, &content_hash(source)).unwrap();
+        assert_eq!(hit.queries[0].file, moved_file);
+        assert_eq!(hit.queries[0].collection, "User");
+
+        fs::write(
+            moved.join("user.ts"),
+            "export const User = model('Account', schema);",
+        )
+        .unwrap();
+        // Dependency hashes are read once per scan, i.e. per loaded cache.
+        assert!(cache.get(&moved_file, &content_hash(source)).is_some());
+        let cache = AnalysisCache::load(&moved);
+        assert!(cache.get(&moved_file, &content_hash(source)).is_none());
+    }
+
+    #[test]
+    fn creating_a_missing_import_invalidates_the_importer() {
+        let dir = TestDir::new("cache-missing-import");
+        let source = "import { Account } from './models/account';\nAccount.find({ email });";
+        let file = dir.write("users.ts", source);
+        let file = file.to_string_lossy();
+
+        let parsed = parse_source(source, &file);
+        assert!(parsed.queries.is_empty());
+        let mut cache = AnalysisCache::new(dir.path());
+        cache.insert(&file, content_hash(source), &parsed);
+        cache.write().unwrap();
+        assert!(AnalysisCache::load(dir.path())
+            .get(&file, &content_hash(source))
+            .is_some());
+
+        dir.write(
+            "models/account/index.ts",
+            "export const Account = model('Account', schema);",
+        );
+        assert!(AnalysisCache::load(dir.path())
+            .get(&file, &content_hash(source))
+            .is_none());
+    }
+}
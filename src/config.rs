@@ -3,24 +3,24 @@ use std::fs;
 use std::io::{Error, ErrorKind};
 use std::path::{Path, PathBuf};
 
-use serde::Deserialize;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
 
-const CONFIG_FILE_NAME: &str = "redshift.config.json";
+use crate::baseline::fingerprint;
+use crate::{MongoQuery, Projection};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+/// File name of the project config, looked up from the analyzed directory.
+pub const CONFIG_FILE_NAME: &str = "redshift.config.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Severity {
     Info,
+    #[default]
     Warning,
     Error,
 }
 
-impl Default for Severity {
-    fn default() -> Self {
-        Self::Warning
-    }
-}
-
 impl Severity {
     pub fn as_str(self) -> &'static str {
         match self {
@@ -38,6 +38,81 @@ pub struct AnalyzerConfig {
     pub defaults: ConfigDefaults,
     #[serde(default)]
     pub collections: Vec<CollectionConfig>,
+    #[serde(default)]
+    pub files: FilesConfig,
+}
+
+/// Build output directories skipped unless `files.defaultExcludes` is false.
+pub const DEFAULT_EXCLUDES: &[&str] = &["**/dist/**", "**/target/**"];
+
+/// Which source files are analyzed, on top of `.gitignore` and
+/// `.redshiftignore`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FilesConfig {
+    /// Globs, relative to the analyzed root, of files to analyze. Empty means
+    /// every source file.
+    pub include: Vec<String>,
+    /// Globs of files to skip, even when included, on top of
+    /// [`DEFAULT_EXCLUDES`].
+    pub exclude: Vec<String>,
+    /// Skip [`DEFAULT_EXCLUDES`] as well as `exclude`.
+    pub default_excludes: bool,
+    /// Honor `.gitignore` files.
+    pub gitignore: bool,
+    /// Larger files are skipped; they are usually generated or bundled.
+    pub max_file_size: u64,
+    pub test_files: TestFiles,
+}
+
+impl Default for FilesConfig {
+    fn default() -> Self {
+        Self {
+            include: Vec::new(),
+            exclude: Vec::new(),
+            default_excludes: true,
+            gitignore: true,
+            max_file_size: 1024 * 1024,
+            test_files: TestFiles::default(),
+        }
+    }
+}
+
+impl FilesConfig {
+    /// Globs of files to skip: the build output directories unless opted
+    /// out of, then `exclude`.
+    pub fn excluded(&self) -> Vec<String> {
+        let defaults = DEFAULT_EXCLUDES
+            .iter()
+            .filter(|_| self.default_excludes)
+            .map(|pattern| pattern.to_string());
+        defaults.chain(self.exclude.iter().cloned()).collect()
+    }
+}
+
+/// What to do with test files (`*.spec.ts`, `*.test.tsx`, files under
+/// `__tests__/`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestFiles {
+    /// Skip them.
+    #[default]
+    Exclude,
+    /// Analyze them like any other file.
+    Include,
+    /// Analyze them, but tag their queries as tests and keep them out of
+    /// rules and index suggestions.
+    Separate,
+}
+
+/// Compiles `patterns` into one matcher. `/` is only matched literally, so
+/// `src/*.ts` does not reach into subdirectories; use `**` for that.
+pub fn glob_set(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(GlobBuilder::new(pattern).literal_separator(true).build()?);
+    }
+    builder.build()
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
@@ -45,6 +120,8 @@ pub struct AnalyzerConfig {
 pub struct ConfigDefaults {
     pub unknown_collection_severity: Option<Severity>,
     pub recommended_predicate_miss_severity: Option<Severity>,
+    /// Lowest finding severity that makes `analyze` exit non-zero.
+    pub fail_on: Option<Severity>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -91,6 +168,7 @@ pub struct PerformanceConfig {
     pub sort_without_index: Option<PerformanceRule>,
     pub regex_without_prefix: Option<PerformanceRule>,
     pub max_fields_in_predicate: Option<ThresholdRule>,
+    pub full_document_read: Option<PerformanceRule>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -112,12 +190,219 @@ pub struct LoadedConfig {
     pub config: AnalyzerConfig,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Stable identifiers of the checks that produce [`ConfigWarning`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rule {
+    UnknownCollection,
+    RecommendedPredicateMiss,
+    FullDocumentRead,
+    UnusedSuppression,
+    SuppressionWithoutReason,
+    ParseError,
+}
+
+impl Rule {
+    pub const ALL: &'static [Rule] = &[
+        Self::UnknownCollection,
+        Self::RecommendedPredicateMiss,
+        Self::FullDocumentRead,
+        Self::UnusedSuppression,
+        Self::SuppressionWithoutReason,
+        Self::ParseError,
+    ];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::UnknownCollection => "unknown-collection",
+            Self::RecommendedPredicateMiss => "recommended-predicate-miss",
+            Self::FullDocumentRead => "full-document-read",
+            Self::UnusedSuppression => "unused-suppression",
+            Self::SuppressionWithoutReason => "suppression-without-reason",
+            Self::ParseError => "parse-error",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|rule| rule.id() == id)
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::UnknownCollection => "Queried collection is not configured",
+            Self::RecommendedPredicateMiss => "Query predicate misses a recommended field",
+            Self::FullDocumentRead => "Query returns full documents",
+            Self::UnusedSuppression => "Suppression comment hides nothing",
+            Self::SuppressionWithoutReason => "Suppression comment gives no reason",
+            Self::ParseError => "Source file has a syntax error",
+        }
+    }
+
+    pub fn help(self) -> &'static str {
+        match self {
+            Self::UnknownCollection => {
+                "Add the collection to `collections` in redshift.config.json so its indexes and \
+                 predicate guidance can be checked, or fix the collection name."
+            }
+            Self::RecommendedPredicateMiss => {
+                "Include one of the collection's recommended fields (for example the tenant key) \
+                 in the filter, as configured in `predicateGuidance`."
+            }
+            Self::FullDocumentRead => {
+                "Pass a projection (or `.select()`) listing only the fields the caller needs. \
+                 Restricting it to the keys of an index, without `_id`, makes the query covered."
+            }
+            Self::UnusedSuppression => {
+                "Remove the comment, or fix the rule ID. It no longer matches a finding on the \
+                 lines it covers."
+            }
+            Self::SuppressionWithoutReason => {
+                "Explain why the finding is acceptable after a colon, e.g. \
+                 `// redshift-ignore-next-line full-document-read: export job`."
+            }
+            Self::ParseError => {
+                "Queries after a fatal syntax error are not analyzed. Fix the error, or leave the \
+                 file out with `files.exclude` or `.redshiftignore` if it is not source code."
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ConfigWarning {
+    pub rule: Rule,
     pub severity: Severity,
     pub message: String,
+    pub collection: Option<String>,
     pub file: Option<String>,
     pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub end_line: Option<usize>,
+    pub end_column: Option<usize>,
+    /// Identifies the finding across edits that only move code; see
+    /// [`crate::baseline`].
+    pub fingerprint: String,
+}
+
+impl ConfigWarning {
+    /// A finding about a collection as a whole, with no source location.
+    pub fn project(rule: Rule, severity: Severity, collection: &str, message: String) -> Self {
+        Self {
+            rule,
+            severity,
+            message,
+            collection: Some(collection.to_string()),
+            fingerprint: fingerprint(&[rule.id(), collection]),
+            file: None,
+            line: None,
+            column: None,
+            end_line: None,
+            end_column: None,
+        }
+    }
+
+    /// A finding located at a query site.
+    ///
+    /// The fingerprint covers the rule, collection, query shape (method and
+    /// filtered fields) and enclosing function, but not the file or line.
+    pub fn at_query(rule: Rule, severity: Severity, message: String, query: &MongoQuery) -> Self {
+        let mut fields: Vec<&str> = query.query_fields.iter().map(String::as_str).collect();
+        fields.sort_unstable();
+        fields.dedup();
+        let function = [query.class_name.as_deref(), query.function_name.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(".");
+
+        Self {
+            rule,
+            severity,
+            message,
+            collection: Some(query.collection.clone()),
+            fingerprint: fingerprint(&[
+                rule.id(),
+                &query.collection,
+                &query.method,
+                &fields.join(","),
+                &function,
+            ]),
+            file: Some(query.file.clone()),
+            line: Some(query.line),
+            column: Some(query.column),
+            end_line: Some(query.end_line),
+            end_column: Some(query.end_column),
+        }
+    }
+
+    /// A finding at a source position that is not a query, such as a
+    /// suppression comment. `key` distinguishes findings of the same rule in
+    /// the fingerprint.
+    pub fn at_location(
+        rule: Rule,
+        severity: Severity,
+        message: String,
+        file: &str,
+        line: usize,
+        column: usize,
+        key: &str,
+    ) -> Self {
+        Self {
+            rule,
+            severity,
+            message,
+            collection: None,
+            fingerprint: fingerprint(&[rule.id(), key]),
+            file: Some(file.to_string()),
+            line: Some(line),
+            column: Some(column),
+            end_line: None,
+            end_column: None,
+        }
+    }
+}
+
+impl IndexConfig {
+    /// Configured name, or the key fields joined with `_` when unnamed.
+    pub fn label(&self) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            self.keys
+                .iter()
+                .map(|key| format!("{}_{}", key.field, key.order))
+                .collect::<Vec<_>>()
+                .join("_")
+        })
+    }
+
+    fn has_field(&self, field: &str) -> bool {
+        self.keys.iter().any(|key| key.field == field)
+    }
+
+    /// Whether the index can serve a predicate on `fields`, i.e. its leading key
+    /// is one of them.
+    pub fn supports(&self, fields: &[String]) -> bool {
+        self.keys
+            .first()
+            .is_some_and(|key| fields.contains(&key.field))
+    }
+
+    /// Whether every filtered field is part of the index, so a projection
+    /// restricted to the index keys would make the query covered.
+    pub fn contains_all(&self, fields: &[String]) -> bool {
+        !fields.is_empty() && fields.iter().all(|field| self.has_field(field))
+    }
+
+    /// Whether the index alone can answer a query filtering on `fields` and
+    /// returning `projection`.
+    pub fn covers(&self, fields: &[String], projection: &Projection) -> bool {
+        let id_excluded = projection.exclude.iter().any(|field| field == "_id");
+        self.supports(fields)
+            && self.contains_all(fields)
+            && projection.is_inclusion()
+            && projection.include.iter().all(|field| self.has_field(field))
+            && (id_excluded || self.has_field("_id"))
+    }
 }
 
 impl ConfigDefaults {
@@ -158,7 +443,8 @@ pub fn load_project_config(
     }))
 }
 
-fn discover_config(start_dir: &Path) -> Option<PathBuf> {
+/// The nearest `redshift.config.json` in `start_dir` or one of its parents.
+pub fn discover_config(start_dir: &Path) -> Option<PathBuf> {
     let mut current = Some(start_dir);
     while let Some(dir) = current {
         let candidate = dir.join(CONFIG_FILE_NAME);
@@ -171,6 +457,10 @@ fn discover_config(start_dir: &Path) -> Option<PathBuf> {
 }
 
 fn validate_config(config: &AnalyzerConfig) -> Result<(), String> {
+    for patterns in [&config.files.include, &config.files.exclude] {
+        glob_set(patterns).map_err(|err| format!("invalid file glob: {}", err))?;
+    }
+
     let mut collections = HashSet::new();
     for collection in &config.collections {
         if collection.name.trim().is_empty() {
@@ -214,7 +504,7 @@ mod tests {
     use std::fs;
     use std::path::PathBuf;
 
-    use super::{discover_config, AnalyzerConfig};
+    use super::{discover_config, validate_config, AnalyzerConfig, TestFiles};
 
     fn unique_temp_dir(prefix: &str) -> PathBuf {
         let nanos = std::time::SystemTime::now()
@@ -254,6 +544,36 @@ mod tests {
         assert_eq!(parsed.collections[0].name, "users");
     }
 
+    #[test]
+    fn parses_file_selection_and_rejects_bad_globs() {
+        let parsed: AnalyzerConfig = serde_json::from_str(
+            r#"{ "files": { "include": ["src/**"], "testFiles": "separate" } }"#,
+        )
+        .unwrap();
+        assert_eq!(parsed.files.include, vec!["src/**"]);
+        assert_eq!(parsed.files.test_files, TestFiles::Separate);
+        assert!(parsed.files.gitignore);
+        assert_eq!(parsed.files.excluded(), vec!["**/dist/**", "**/target/**"]);
+
+        let parsed: AnalyzerConfig =
+            serde_json::from_str(r#"{ "files": { "exclude": ["src/generated/**"] } }"#).unwrap();
+        assert_eq!(
+            parsed.files.excluded(),
+            vec!["**/dist/**", "**/target/**", "src/generated/**"]
+        );
+        let parsed: AnalyzerConfig = serde_json::from_str(
+            r#"{ "files": { "exclude": ["src/generated/**"], "defaultExcludes": false } }"#,
+        )
+        .unwrap();
+        assert_eq!(parsed.files.excluded(), vec!["src/generated/**"]);
+
+        let invalid: AnalyzerConfig =
+            serde_json::from_str(r#"{ "files": { "exclude": ["src/[a"] } }"#).unwrap();
+        assert!(validate_config(&invalid)
+            .unwrap_err()
+            .starts_with("invalid file glob"));
+    }
+
     #[test]
     fn discovers_config_in_parent_directory() {
         let root = unique_temp_dir("redshift_config_test");
@@ -268,4 +588,31 @@ mod tests {
 
         fs::remove_dir_all(&root).unwrap();
     }
+
+    #[test]
+    fn index_covers_projection_of_its_keys() {
+        let index: super::IndexConfig = serde_json::from_str(
+            r#"{
+                "keys": [
+                    { "field": "organizationId", "order": 1 },
+                    { "field": "email", "order": 1 }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let fields = vec!["organizationId".to_string()];
+        let covered = crate::Projection {
+            include: vec!["email".to_string()],
+            exclude: vec!["_id".to_string()],
+        };
+        let keeps_id = crate::Projection {
+            include: vec!["email".to_string()],
+            exclude: Vec::new(),
+        };
+
+        assert!(index.covers(&fields, &covered));
+        assert!(!index.covers(&fields, &keeps_id));
+        assert!(!index.covers(&["email".to_string()], &covered));
+    }
 }
@@ -0,0 +1,421 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::ConfigWarning;
+use crate::report::CollectionIndexes;
+use crate::{relative_path, IndexCoverage, IndexSuggestion};
+
+/// The parts of an `analyze --format json` document a diff looks at. Other
+/// fields are ignored, so reports from older or newer versions still load as
+/// long as these are present.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Snapshot {
+    root: String,
+    collections: Vec<SnapshotCollection>,
+    index_suggestions: Vec<CollectionIndexes>,
+    #[serde(default)]
+    index_coverage: Vec<IndexCoverage>,
+    warnings: Vec<ConfigWarning>,
+}
+
+impl Snapshot {
+    /// Reads the snapshot and makes finding locations relative to its root,
+    /// so findings of two checkouts compare and print alike.
+    fn load(report: &Value) -> Result<Self, Box<dyn Error>> {
+        let mut snapshot = Self::deserialize(report)?;
+        let root = Path::new(&snapshot.root);
+        for warning in &mut snapshot.warnings {
+            if let Some(file) = &mut warning.file {
+                *file = relative_path(root, file);
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SnapshotCollection {
+    collection: String,
+    files: Vec<SnapshotFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SnapshotFile {
+    patterns: Vec<SnapshotPattern>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SnapshotPattern {
+    shape_id: String,
+    shape: String,
+    fields: Vec<String>,
+    queries: Vec<SnapshotSite>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SnapshotSite {
+    file: String,
+    line: usize,
+    method: String,
+    class_name: Option<String>,
+    function_name: Option<String>,
+}
+
+/// A query shape present on one side of a diff.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShapeDelta {
+    pub id: String,
+    pub collection: String,
+    pub shape: String,
+    /// `file:line` of every site with this shape, relative to its root.
+    pub sites: Vec<String>,
+    /// No configured index supports the shape's filter fields. Always false
+    /// for collections without configured indexes.
+    pub unindexed: bool,
+}
+
+/// Call sites whose shape changed: the same enclosing function queries the
+/// same collection with the same method, but with a different shape.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShapeChange {
+    pub before: ShapeDelta,
+    pub after: ShapeDelta,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexSuggestionDelta {
+    pub collection: String,
+    pub suggestion: String,
+}
+
+/// Query and index drift between two analyses of the same project.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisDiff {
+    pub added_shapes: Vec<ShapeDelta>,
+    pub removed_shapes: Vec<ShapeDelta>,
+    pub changed_shapes: Vec<ShapeChange>,
+    /// Findings whose fingerprint is new, or more frequent than before. File
+    /// paths are relative to the analyzed root.
+    pub new_warnings: Vec<ConfigWarning>,
+    pub resolved_warnings: Vec<ConfigWarning>,
+    pub added_index_suggestions: Vec<IndexSuggestionDelta>,
+    pub removed_index_suggestions: Vec<IndexSuggestionDelta>,
+}
+
+impl AnalysisDiff {
+    /// Compares two `analyze --format json` documents, `old` first.
+    pub fn between(old: &Value, new: &Value) -> Result<Self, Box<dyn Error>> {
+        let old = Snapshot::load(old)?;
+        let new = Snapshot::load(new)?;
+
+        let old_shapes = shapes(&old);
+        let new_shapes = shapes(&new);
+        let mut added: Vec<(ShapeDelta, Vec<String>)> = new_shapes
+            .iter()
+            .filter(|(id, _)| !old_shapes.contains_key(*id))
+            .map(|(_, shape)| shape.clone())
+            .collect();
+        let mut removed: Vec<(ShapeDelta, Vec<String>)> = old_shapes
+            .iter()
+            .filter(|(id, _)| !new_shapes.contains_key(*id))
+            .map(|(_, shape)| shape.clone())
+            .collect();
+
+        // A removed and an added shape sharing a call site are one query that
+        // changed, not two unrelated ones.
+        let mut changed_shapes = Vec::new();
+        removed.retain(|(before, before_sites)| {
+            let Some(index) = added.iter().position(|(_, after_sites)| {
+                after_sites.iter().any(|site| before_sites.contains(site))
+            }) else {
+                return true;
+            };
+            let (after, _) = added.remove(index);
+            changed_shapes.push(ShapeChange {
+                before: before.clone(),
+                after,
+            });
+            false
+        });
+
+        let (new_warnings, resolved_warnings) = warning_changes(&old.warnings, &new.warnings);
+        let old_suggestions = index_suggestions(&old);
+        let new_suggestions = index_suggestions(&new);
+
+        Ok(Self {
+            added_shapes: added.into_iter().map(|(shape, _)| shape).collect(),
+            removed_shapes: removed.into_iter().map(|(shape, _)| shape).collect(),
+            changed_shapes,
+            new_warnings,
+            resolved_warnings,
+            added_index_suggestions: suggestion_changes(&new_suggestions, &old_suggestions),
+            removed_index_suggestions: suggestion_changes(&old_suggestions, &new_suggestions),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added_shapes.is_empty()
+            && self.removed_shapes.is_empty()
+            && self.changed_shapes.is_empty()
+            && self.new_warnings.is_empty()
+            && self.resolved_warnings.is_empty()
+            && self.added_index_suggestions.is_empty()
+            && self.removed_index_suggestions.is_empty()
+    }
+
+    /// A summary for terminals and pull request comments: one line per
+    /// change, `+` for added, `-` for removed and `~` for changed.
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        if self.is_empty() {
+            out.push_str("No query or index drift.\n");
+            return out;
+        }
+
+        if !self.added_shapes.is_empty()
+            || !self.removed_shapes.is_empty()
+            || !self.changed_shapes.is_empty()
+        {
+            out.push_str("Query shapes:\n");
+            for shape in &self.added_shapes {
+                let _ = writeln!(out, "  + {}{}", describe_shape(shape), unindexed(shape));
+            }
+            for change in &self.changed_shapes {
+                let _ = writeln!(
+                    out,
+                    "  ~ {}{}\n      was {}",
+                    describe_shape(&change.after),
+                    unindexed(&change.after),
+                    change.before.shape
+                );
+            }
+            for shape in &self.removed_shapes {
+                let _ = writeln!(out, "  - {}", describe_shape(shape));
+            }
+            out.push('\n');
+        }
+
+        for (heading, sign, warnings) in [
+            ("New findings", '+', &self.new_warnings),
+            ("Resolved findings", '-', &self.resolved_warnings),
+        ] {
+            if warnings.is_empty() {
+                continue;
+            }
+            let _ = writeln!(out, "{}:", heading);
+            for warning in warnings {
+                let location = match (&warning.file, warning.line) {
+                    (Some(file), Some(line)) => format!(" ({}:{})", file, line),
+                    _ => String::new(),
+                };
+                let _ = writeln!(
+                    out,
+                    "  {} {}[{}]: {}{}",
+                    sign,
+                    warning.severity.as_str(),
+                    warning.rule.id(),
+                    warning.message,
+                    location
+                );
+            }
+            out.push('\n');
+        }
+
+        if !self.added_index_suggestions.is_empty() || !self.removed_index_suggestions.is_empty() {
+            out.push_str("Index suggestions:\n");
+            for (sign, suggestions) in [
+                ('+', &self.added_index_suggestions),
+                ('-', &self.removed_index_suggestions),
+            ] {
+                for suggestion in suggestions {
+                    let _ = writeln!(
+                        out,
+                        "  {} {}: {}",
+                        sign, suggestion.collection, suggestion.suggestion
+                    );
+                }
+            }
+            out.push('\n');
+        }
+
+        out
+    }
+}
+
+fn describe_shape(shape: &ShapeDelta) -> String {
+    format!("{} at {}", shape.shape, shape.sites.join(", "))
+}
+
+fn unindexed(shape: &ShapeDelta) -> &'static str {
+    if shape.unindexed {
+        " (unindexed)"
+    } else {
+        ""
+    }
+}
+
+/// Shapes by ID, each with the call sites used to pair changed shapes:
+/// file, class, function and method, but not the line, which shifts with
+/// unrelated edits.
+fn shapes(snapshot: &Snapshot) -> BTreeMap<String, (ShapeDelta, Vec<String>)> {
+    let root = Path::new(&snapshot.root);
+    let mut shapes: BTreeMap<String, (ShapeDelta, Vec<String>)> = BTreeMap::new();
+
+    for collection in &snapshot.collections {
+        let coverage = snapshot
+            .index_coverage
+            .iter()
+            .find(|coverage| coverage.collection == collection.collection);
+        for pattern in collection.files.iter().flat_map(|file| &file.patterns) {
+            let (shape, sites) = shapes.entry(pattern.shape_id.clone()).or_insert_with(|| {
+                (
+                    ShapeDelta {
+                        id: pattern.shape_id.clone(),
+                        collection: collection.collection.clone(),
+                        shape: pattern.shape.clone(),
+                        sites: Vec::new(),
+                        unindexed: coverage.is_some_and(|coverage| {
+                            coverage.unsupported_patterns.contains(&pattern.fields)
+                        }),
+                    },
+                    Vec::new(),
+                )
+            });
+            for query in &pattern.queries {
+                let file = relative_path(root, &query.file);
+                shape.sites.push(format!("{}:{}", file, query.line));
+                sites.push(format!(
+                    "{}\0{}\0{}\0{}",
+                    file,
+                    query.class_name.as_deref().unwrap_or_default(),
+                    query.function_name.as_deref().unwrap_or_default(),
+                    query.method
+                ));
+            }
+        }
+    }
+
+    for (shape, _) in shapes.values_mut() {
+        shape.sites.sort();
+    }
+    shapes
+}
+
+/// Findings in `new` beyond what `old` had, and the reverse. Findings are
+/// matched by fingerprint and counted, as a baseline does.
+pub fn warning_changes(
+    old: &[ConfigWarning],
+    new: &[ConfigWarning],
+) -> (Vec<ConfigWarning>, Vec<ConfigWarning>) {
+    let unmatched = |from: &[ConfigWarning], against: &[ConfigWarning]| {
+        let mut available: BTreeMap<&str, usize> = BTreeMap::new();
+        for warning in against {
+            *available.entry(&warning.fingerprint).or_default() += 1;
+        }
+        from.iter()
+            .filter(
+                |warning| match available.get_mut(warning.fingerprint.as_str()) {
+                    Some(count) if *count > 0 => {
+                        *count -= 1;
+                        false
+                    }
+                    _ => true,
+                },
+            )
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    (unmatched(new, old), unmatched(old, new))
+}
+
+/// Suggestions keyed by collection and indexed fields, so a suggestion whose
+/// usage count merely changed is not reported.
+fn index_suggestions(snapshot: &Snapshot) -> BTreeMap<(String, String), String> {
+    snapshot
+        .index_suggestions
+        .iter()
+        .flat_map(|collection| {
+            collection.suggestions.iter().map(|suggestion| {
+                let key = match suggestion {
+                    IndexSuggestion::SingleField { field, .. } => field.clone(),
+                    IndexSuggestion::Compound { fields } => fields.join(","),
+                };
+                ((collection.collection.clone(), key), suggestion.to_string())
+            })
+        })
+        .collect()
+}
+
+fn suggestion_changes(
+    from: &BTreeMap<(String, String), String>,
+    against: &BTreeMap<(String, String), String>,
+) -> Vec<IndexSuggestionDelta> {
+    from.iter()
+        .filter(|(key, _)| !against.contains_key(*key))
+        .map(|((collection, _), suggestion)| IndexSuggestionDelta {
+            collection: collection.clone(),
+            suggestion: suggestion.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::AnalysisDiff;
+    use crate::ast_parser::parse_file;
+    use crate::report::AnalysisReport;
+
+    fn report(source: &str) -> serde_json::Value {
+        let queries = parse_file(source, "src/orders.service.ts");
+        serde_json::to_value(AnalysisReport::new(Path::new("."), queries, None)).unwrap()
+    }
+
+    #[test]
+    fn pairs_changed_call_sites_and_lists_new_shapes() {
+        let old = report(
+            r#"
+            class OrderService {
+                byUser(userId) { return db.collection('orders').find({ userId }); }
+                recent() { return db.collection('orders').find({}).sort({ createdAt: -1 }); }
+            }
+            "#,
+        );
+        let new = report(
+            r#"
+            class OrderService {
+                byUser(userId) { return db.collection('orders').find({ userId, deleted: false }); }
+                open() { return db.collection('orders').find({ status: 'open' }); }
+            }
+            "#,
+        );
+
+        let diff = AnalysisDiff::between(&old, &new).unwrap();
+
+        assert_eq!(diff.added_shapes.len(), 1);
+        assert_eq!(diff.added_shapes[0].shape, "orders.find({status:$eq})");
+        assert_eq!(diff.added_shapes[0].sites, vec!["src/orders.service.ts:4"]);
+        assert_eq!(diff.changed_shapes.len(), 1);
+        assert_eq!(
+            diff.changed_shapes[0].after.shape,
+            "orders.find({deleted:$eq, userId:$eq})"
+        );
+        assert_eq!(diff.removed_shapes.len(), 1);
+        assert!(AnalysisDiff::between(&new, &new).unwrap().is_empty());
+    }
+}
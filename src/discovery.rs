@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use globset::{GlobBuilder, GlobMatcher, GlobSet};
+use walkdir::WalkDir;
+
+use crate::config::{glob_set, FilesConfig, TestFiles};
+use crate::{relative_path, FileCategory, Language};
+
+/// Directories never worth descending into, whatever the configuration.
+const ALWAYS_IGNORED_DIRS: &[&str] = &["node_modules", ".git"];
+
+/// Per-directory ignore files, read in this order; later rules win.
+const IGNORE_FILES: &[&str] = &[".gitignore", ".redshiftignore"];
+
+/// A file selected for analysis.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub category: FileCategory,
+}
+
+/// One line of a `.gitignore`-style file.
+struct IgnoreRule {
+    matcher: GlobMatcher,
+    negated: bool,
+    dir_only: bool,
+}
+
+/// Source files under `root` selected by `files`, `.gitignore` (when
+/// enabled) and `.redshiftignore`, ordered by path.
+///
+/// Globs are expected to be valid, as checked when the config is loaded;
+/// invalid ones match nothing.
+pub fn find_source_files(root: &Path, files: &FilesConfig) -> Vec<SourceFile> {
+    let include = glob_set(&files.include).unwrap_or_else(|_| GlobSet::empty());
+    let exclude = glob_set(&files.excluded()).unwrap_or_else(|_| GlobSet::empty());
+    let ignore_files: Vec<&str> = IGNORE_FILES
+        .iter()
+        .copied()
+        .filter(|name| files.gitignore || *name != ".gitignore")
+        .collect();
+    let mut rules: HashMap<PathBuf, Vec<IgnoreRule>> = HashMap::new();
+
+    let mut found: Vec<SourceFile> = WalkDir::new(root)
+        .into_iter()
+        .filter_entry(|entry| {
+            if entry.depth() == 0 {
+                return true;
+            }
+            let is_dir = entry.file_type().is_dir();
+            let name = entry.file_name().to_str().unwrap_or("");
+            if is_dir && ALWAYS_IGNORED_DIRS.contains(&name) {
+                return false;
+            }
+            !is_ignored(root, entry.path(), is_dir, &ignore_files, &mut rules)
+        })
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| Language::of(&entry.file_name().to_string_lossy()).is_some())
+        .filter_map(|entry| {
+            let relative = relative_path(root, &entry.path().to_string_lossy());
+            if (!files.include.is_empty() && !include.is_match(&relative))
+                || exclude.is_match(&relative)
+            {
+                return None;
+            }
+
+            let category = category_of(&relative, files)?;
+
+            let size = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            if size > files.max_file_size {
+                eprintln!(
+                    "Warning: Skipping {}: {} bytes exceeds the {} byte limit (files.maxFileSize)",
+                    entry.path().display(),
+                    size,
+                    files.max_file_size
+                );
+                return None;
+            }
+
+            Some(SourceFile {
+                path: entry.into_path(),
+                category,
+            })
+        })
+        .collect();
+
+    found.sort_by(|a, b| a.path.cmp(&b.path));
+    found
+}
+
+/// The category of the file at `relative`, or `None` when it is a test file
+/// and tests are excluded.
+pub fn category_of(relative: &str, files: &FilesConfig) -> Option<FileCategory> {
+    if !is_test_file(relative) {
+        return Some(FileCategory::Source);
+    }
+    match files.test_files {
+        TestFiles::Exclude => None,
+        TestFiles::Include => Some(FileCategory::Source),
+        TestFiles::Separate => Some(FileCategory::Test),
+    }
+}
+
+/// `*.spec.*` and `*.test.*` files, and anything under `__tests__/`.
+pub fn is_test_file(relative: &str) -> bool {
+    let mut components = relative.split('/').rev();
+    let name = components.next().unwrap_or_default();
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    stem.ends_with(".spec")
+        || stem.ends_with(".test")
+        || components.any(|component| component == "__tests__")
+}
+
+/// Whether the ignore files between `root` and `path` exclude it. As in git,
+/// the deepest matching rule wins and `!` re-includes.
+fn is_ignored(
+    root: &Path,
+    path: &Path,
+    is_dir: bool,
+    ignore_files: &[&str],
+    rules: &mut HashMap<PathBuf, Vec<IgnoreRule>>,
+) -> bool {
+    let mut directories: Vec<&Path> = path
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(root))
+        .collect();
+    directories.reverse();
+
+    let mut ignored = false;
+    for dir in directories {
+        let dir_rules = rules
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| read_ignore_rules(dir, ignore_files));
+        let relative = relative_path(dir, &path.to_string_lossy());
+        for rule in dir_rules.iter() {
+            if (is_dir || !rule.dir_only) && rule.matcher.is_match(&relative) {
+                ignored = !rule.negated;
+            }
+        }
+    }
+    ignored
+}
+
+fn read_ignore_rules(dir: &Path, ignore_files: &[&str]) -> Vec<IgnoreRule> {
+    ignore_files
+        .iter()
+        .filter_map(|name| fs::read_to_string(dir.join(name)).ok())
+        .flat_map(|content| {
+            content
+                .lines()
+                .filter_map(parse_ignore_line)
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Translates a `.gitignore` line into a glob relative to the file's
+/// directory: patterns without an inner `/` match at any depth, a trailing
+/// `/` matches only directories, and `!` negates.
+fn parse_ignore_line(line: &str) -> Option<IgnoreRule> {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let (negated, pattern) = match line.strip_prefix('!') {
+        Some(pattern) => (true, pattern),
+        None => (false, line.strip_prefix('\\').unwrap_or(line)),
+    };
+    let (dir_only, pattern) = match pattern.strip_suffix('/') {
+        Some(pattern) => (true, pattern),
+        None => (false, pattern),
+    };
+    let glob = if pattern.contains('/') {
+        pattern.trim_start_matches('/').to_string()
+    } else {
+        format!("**/{}", pattern)
+    };
+
+    let matcher = GlobBuilder::new(&glob)
+        .literal_separator(true)
+        .build()
+        .ok()?
+        .compile_matcher();
+    Some(IgnoreRule {
+        matcher,
+        negated,
+        dir_only,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{find_source_files, is_test_file};
+    use crate::config::{FilesConfig, TestFiles};
+    use crate::{relative_path, FileCategory, TestDir};
+
+    #[test]
+    fn honors_ignore_files_globs_size_and_test_category() {
+        let dir = TestDir::new("discovery");
+        let root = dir.path();
+        for dir in ["src/generated", "src/legacy", "dist", "scripts"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "src/user.service.ts",
+            "src/user.service.spec.tsx",
+            "src/generated/client.ts",
+            "src/legacy/old.ts",
+            "src/legacy/keep.ts",
+            "dist/bundle.ts",
+            "scripts/seed.ts",
+        ] {
+            fs::write(root.join(file), "").unwrap();
+        }
+        fs::write(root.join("src/big.ts"), "x".repeat(64)).unwrap();
+        fs::write(root.join(".gitignore"), "generated/\n").unwrap();
+        fs::write(root.join("src/.redshiftignore"), "legacy/*\n!keep.ts\n").unwrap();
+
+        let files = FilesConfig {
+            exclude: vec!["scripts/**".to_string()],
+            max_file_size: 32,
+            test_files: TestFiles::Separate,
+            ..FilesConfig::default()
+        };
+        let found = |files: &FilesConfig| -> Vec<(String, FileCategory)> {
+            find_source_files(root, files)
+                .into_iter()
+                .map(|file| {
+                    (
+                        relative_path(root, &file.path.to_string_lossy()),
+                        file.category,
+                    )
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            found(&files),
+            vec![
+                ("src/legacy/keep.ts".to_string(), FileCategory::Source),
+                ("src/user.service.spec.tsx".to_string(), FileCategory::Test),
+                ("src/user.service.ts".to_string(), FileCategory::Source),
+            ]
+        );
+        let with_build_output = FilesConfig {
+            default_excludes: false,
+            ..files
+        };
+        assert_eq!(found(&with_build_output)[0].0, "dist/bundle.ts");
+        assert!(is_test_file("src/__tests__/helpers.ts"));
+        assert!(!is_test_file("src/contest.ts"));
+    }
+}
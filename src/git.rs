@@ -0,0 +1,41 @@
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+/// Files under `root` that differ from `since`: committed, staged and
+/// unstaged changes, plus untracked files that are not ignored. Paths are
+/// relative to `root` with `/` separators, like [`crate::relative_path`].
+pub fn changed_files(root: &Path, since: &str) -> Result<BTreeSet<String>, Box<dyn Error>> {
+    let mut files = git_lines(root, &["diff", "--name-only", "--relative", since, "--"])?;
+    files.extend(git_lines(
+        root,
+        &["ls-files", "--others", "--exclude-standard"],
+    )?);
+    Ok(files)
+}
+
+fn git_lines(root: &Path, args: &[&str]) -> Result<BTreeSet<String>, Box<dyn Error>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .output()
+        .map_err(|err| io::Error::new(err.kind(), format!("Failed to run git: {}", err)))?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
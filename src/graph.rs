@@ -0,0 +1,258 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::{get_collection_analysis, relative_path, MongoQuery, OperationClass};
+
+/// What the non-collection nodes of a [`CollectionGraph`] stand for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphGrouping {
+    /// One node per source file, relative to the analyzed root.
+    Module,
+    /// One node per class; queries outside any class fall back to their file.
+    Class,
+}
+
+/// How often one module or class reads and writes one collection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Access {
+    pub reads: usize,
+    /// Updates, deletes and inserts.
+    pub writes: usize,
+}
+
+/// Modules or classes linked to the collections they touch, plus
+/// collection-to-collection edges from `$lookup`-style stages and `populate`.
+#[derive(Debug, Default)]
+pub struct CollectionGraph {
+    /// Keyed by `(module or class, collection)`.
+    pub accesses: BTreeMap<(String, String), Access>,
+    /// `(collection, joined collection, via)`.
+    pub joins: BTreeSet<(String, String, String)>,
+}
+
+impl CollectionGraph {
+    pub fn build(root: &Path, queries: &[MongoQuery], grouping: GraphGrouping) -> Self {
+        let mut graph = Self::default();
+
+        for collection in get_collection_analysis(queries) {
+            for file in &collection.files {
+                let module = relative_path(root, &file.file_path);
+                for query in file.patterns.iter().flat_map(|pattern| &pattern.queries) {
+                    let source = match (grouping, &query.class_name) {
+                        (GraphGrouping::Class, Some(class_name)) => class_name.clone(),
+                        _ => module.clone(),
+                    };
+                    let access = graph
+                        .accesses
+                        .entry((source, collection.collection.clone()))
+                        .or_default();
+                    match query.operation {
+                        OperationClass::Read => access.reads += 1,
+                        _ => access.writes += 1,
+                    }
+
+                    // A join whose collection is unknown would be drawn as a
+                    // made-up node, so it is left out.
+                    for join in &query.joins {
+                        if let Some(from) = &join.from {
+                            graph.joins.insert((
+                                collection.collection.clone(),
+                                from.clone(),
+                                join.via.clone(),
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        graph
+    }
+
+    fn sources(&self) -> BTreeSet<&str> {
+        self.accesses
+            .keys()
+            .map(|(source, _)| source.as_str())
+            .collect()
+    }
+
+    fn collections(&self) -> BTreeSet<&str> {
+        self.accesses
+            .keys()
+            .map(|(_, collection)| collection.as_str())
+            .chain(
+                self.joins
+                    .iter()
+                    .flat_map(|(from, to, _)| [from.as_str(), to.as_str()]),
+            )
+            .collect()
+    }
+
+    /// Graphviz DOT. Collections are cylinders; write edges are red.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        out.push_str("digraph redshift {\n");
+        out.push_str("  rankdir=LR;\n");
+        out.push_str("  node [shape=box];\n");
+
+        for source in self.sources() {
+            let _ = writeln!(out, "  {};", dot_id(source));
+        }
+        for collection in self.collections() {
+            let _ = writeln!(
+                out,
+                "  {} [shape=cylinder, label={}];",
+                dot_id(&collection_node(collection)),
+                dot_id(collection)
+            );
+        }
+        for ((source, collection), access) in &self.accesses {
+            let target = dot_id(&collection_node(collection));
+            if access.reads > 0 {
+                let _ = writeln!(
+                    out,
+                    "  {} -> {} [label=\"read {}\"];",
+                    dot_id(source),
+                    target,
+                    access.reads
+                );
+            }
+            if access.writes > 0 {
+                let _ = writeln!(
+                    out,
+                    "  {} -> {} [label=\"write {}\", color=firebrick];",
+                    dot_id(source),
+                    target,
+                    access.writes
+                );
+            }
+        }
+        for (from, to, via) in &self.joins {
+            let _ = writeln!(
+                out,
+                "  {} -> {} [label={}, style=dashed];",
+                dot_id(&collection_node(from)),
+                dot_id(&collection_node(to)),
+                dot_id(via)
+            );
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    /// Mermaid flowchart. Write edges are thick; joins are dotted.
+    pub fn to_mermaid(&self) -> String {
+        let mut ids: BTreeMap<String, String> = BTreeMap::new();
+        let mut out = String::new();
+        out.push_str("flowchart LR\n");
+
+        for (index, source) in self.sources().into_iter().enumerate() {
+            let id = format!("m{}", index);
+            let _ = writeln!(out, "  {}[\"{}\"]", id, mermaid_label(source));
+            ids.insert(source.to_string(), id);
+        }
+        for (index, collection) in self.collections().into_iter().enumerate() {
+            let id = format!("c{}", index);
+            let _ = writeln!(out, "  {}[(\"{}\")]", id, mermaid_label(collection));
+            ids.insert(collection_node(collection), id);
+        }
+        for ((source, collection), access) in &self.accesses {
+            let target = &ids[&collection_node(collection)];
+            if access.reads > 0 {
+                let _ = writeln!(
+                    out,
+                    "  {} -->|\"read {}\"| {}",
+                    ids[source], access.reads, target
+                );
+            }
+            if access.writes > 0 {
+                let _ = writeln!(
+                    out,
+                    "  {} ==>|\"write {}\"| {}",
+                    ids[source], access.writes, target
+                );
+            }
+        }
+        for (from, to, via) in &self.joins {
+            let _ = writeln!(
+                out,
+                "  {} -.->|\"{}\"| {}",
+                ids[&collection_node(from)],
+                mermaid_label(via),
+                ids[&collection_node(to)]
+            );
+        }
+
+        out
+    }
+}
+
+/// Collections get their own namespace so a class named like a collection
+/// does not collapse into it.
+fn collection_node(collection: &str) -> String {
+    format!("collection:{}", collection)
+}
+
+fn dot_id(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn mermaid_label(name: &str) -> String {
+    name.replace('"', "#quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{CollectionGraph, GraphGrouping};
+    use crate::{Join, MongoQuery, OperationClass};
+
+    #[test]
+    fn groups_accesses_by_class_and_keeps_join_edges() {
+        let queries = vec![
+            MongoQuery {
+                file: "app/src/order.service.ts".to_string(),
+                collection: "orders".to_string(),
+                method: "aggregate".to_string(),
+                class_name: Some("OrderService".to_string()),
+                joins: vec![
+                    Join {
+                        from: Some("users".to_string()),
+                        path: None,
+                        via: "$lookup".to_string(),
+                    },
+                    Join {
+                        from: None,
+                        path: Some("author".to_string()),
+                        via: "populate".to_string(),
+                    },
+                ],
+                ..Default::default()
+            },
+            MongoQuery {
+                file: "app/src/order.service.ts".to_string(),
+                collection: "orders".to_string(),
+                method: "updateOne".to_string(),
+                operation: OperationClass::Write,
+                class_name: Some("OrderService".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        let graph = CollectionGraph::build(Path::new("app"), &queries, GraphGrouping::Class);
+        let dot = graph.to_dot();
+        let mermaid = graph.to_mermaid();
+
+        assert!(dot.contains("\"OrderService\" -> \"collection:orders\" [label=\"read 1\"];"));
+        assert!(dot.contains("[label=\"write 1\", color=firebrick];"));
+        assert!(dot.contains(
+            "\"collection:orders\" -> \"collection:users\" [label=\"$lookup\", style=dashed];"
+        ));
+        assert!(mermaid.contains("m0[\"OrderService\"]"));
+        assert!(mermaid.contains("c0 -.->|\"$lookup\"| c1"));
+        assert!(!dot.contains("author") && !mermaid.contains("author"));
+    }
+}
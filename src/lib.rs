@@ -1,25 +1,257 @@
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
 use std::fmt;
 use std::fs;
+use std::io;
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 
 pub mod ast_parser;
+pub mod baseline;
+pub mod cache;
 pub mod config;
+pub mod diff;
+pub mod discovery;
+pub mod git;
+pub mod graph;
+pub mod lsp;
+pub mod models;
+pub mod report;
+pub mod shape;
+pub mod suppression;
+pub mod watch;
 
-use crate::config::{AnalyzerConfig, ConfigWarning, Severity};
+use crate::ast_parser::{ParseError, ParsedFile};
+use crate::cache::{content_hash, AnalysisCache};
+use crate::config::{AnalyzerConfig, ConfigWarning, FilesConfig, Rule, Severity};
+use crate::discovery::{find_source_files, SourceFile};
+use crate::shape::QueryShape;
+use crate::suppression::Suppression;
 
-#[derive(Debug, Clone, PartialEq)]
+/// Broad class of a query site, so reads can be reported separately from writes.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationClass {
+    #[default]
+    Read,
+    Write,
+    Delete,
+    Insert,
+}
+
+impl OperationClass {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::Delete => "delete",
+            Self::Insert => "insert",
+        }
+    }
+}
+
+impl fmt::Display for OperationClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Whether a query comes from application code or from a test file analyzed
+/// with `files.testFiles: "separate"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileCategory {
+    #[default]
+    Source,
+    Test,
+}
+
+/// The language a query's source file is written in, from its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    /// `.ts` and `.tsx`.
+    #[default]
+    TypeScript,
+    /// `.js`, `.mjs`, `.cjs` and `.jsx`.
+    JavaScript,
+}
+
+impl Language {
+    /// The language of `path`, or `None` for files that are not analyzed.
+    pub fn of(path: &str) -> Option<Self> {
+        match path.rsplit_once('.')?.1 {
+            "ts" | "tsx" => Some(Self::TypeScript),
+            "js" | "mjs" | "cjs" | "jsx" => Some(Self::JavaScript),
+            _ => None,
+        }
+    }
+}
+
+/// How much of a query's arguments could be read statically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResolutionStatus {
+    /// Every argument that shapes the query was read.
+    #[default]
+    Resolved,
+    /// The filter was read, but some of it, or another argument such as the
+    /// update document, was not.
+    Partial,
+    /// The filter or pipeline could not be read at all, so the query's fields
+    /// are unknown.
+    Unresolved,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Resolution {
+    pub status: ResolutionStatus,
+    /// What could not be read, e.g. "filter is built by a function call".
+    pub reasons: Vec<String>,
+}
+
+/// Links a query expanded from a `bulkWrite` operations array back to the call
+/// that contains it.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParentQuery {
+    pub method: String,
+    pub line: usize,
+    /// Position of the operation inside the operations array.
+    pub index: usize,
+}
+
+/// Fields returned by a read, from a projection document, a Mongoose
+/// `select()` or the final `$project` stage of a pipeline.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Projection {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl Projection {
+    pub(crate) fn normalize(&mut self) {
+        self.include.sort();
+        self.include.dedup();
+        self.exclude.sort();
+        self.exclude.dedup();
+    }
+
+    /// Whether only the listed fields (plus `_id` unless excluded) are returned.
+    pub fn is_inclusion(&self) -> bool {
+        !self.include.is_empty()
+    }
+
+    /// Whether no field is listed, so full documents are returned.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SortKey {
+    pub field: String,
+    /// `1` for ascending, `-1` for descending.
+    pub order: i32,
+}
+
+/// Behavior set through a driver options argument or the equivalent chained
+/// modifier (`.sort()`, `.limit()`, `.hint()`, ...). Values that are not
+/// literals are kept as written in the source.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryOptions {
+    pub sort: Vec<SortKey>,
+    pub limit: Option<String>,
+    pub skip: Option<String>,
+    pub hint: Option<String>,
+    /// Collation locale, or the collation expression when not a literal.
+    pub collation: Option<String>,
+    pub session: bool,
+    pub read_preference: Option<String>,
+    #[serde(rename = "maxTimeMS")]
+    pub max_time_ms: Option<String>,
+    pub upsert: bool,
+    /// Identifiers declared by `arrayFilters`, e.g. `item` for `{ 'item.sku': 1 }`.
+    pub array_filters: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct MongoQuery {
     pub file: String,
+    /// 1-based start line of the call.
     pub line: usize,
+    /// 1-based start column of the call.
+    pub column: usize,
+    pub end_line: usize,
+    /// 1-based column just past the end of the call.
+    pub end_column: usize,
     pub collection: String,
     pub method: String,
+    pub operation: OperationClass,
     pub query_fields: Vec<String>,
+    /// Filter terms as `field:$operator` (`$eq` for plain values), plus
+    /// top-level operators such as `$or` or `$text`.
+    pub predicate: Vec<String>,
+    /// Stage names of an aggregation pipeline, in order.
+    pub pipeline: Vec<String>,
+    /// Paths written by the update, replacement or inserted document(s), with
+    /// positional operators removed.
+    pub written_fields: Vec<String>,
+    /// Returned-field set; `None` when the query returns whole documents.
+    pub projection: Option<Projection>,
+    pub options: QueryOptions,
     pub raw_match: String,
+    pub parent: Option<ParentQuery>,
+    /// Class declaring the method the query sits in, if any.
+    pub class_name: Option<String>,
+    /// Innermost named function or method containing the query.
+    pub function_name: Option<String>,
+    /// Other collections pulled in by `$lookup`-style stages or `populate`.
+    pub joins: Vec<Join>,
+    /// Whether the file is application code or a test.
+    #[serde(default)]
+    pub category: FileCategory,
+    #[serde(default)]
+    pub language: Language,
+    #[serde(default)]
+    pub resolution: Resolution,
+}
+
+impl MongoQuery {
+    /// Whether this is a document read (`find`, `findOne`, `findById`) with no
+    /// projection, so every field of every matched document is returned.
+    pub fn returns_full_document(&self) -> bool {
+        self.projection.is_none() && matches!(self.method.as_str(), "find" | "findOne" | "findById")
+    }
+}
+
+/// A collection read alongside the queried one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Join {
+    /// Joined collection; for `populate`, the `model`, which names the
+    /// collection the way queries through that model do. `None` when
+    /// `populate` gives only a path, since the referenced model is named in
+    /// the schema, which is not analyzed.
+    pub from: Option<String>,
+    /// The populated path, for `populate`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// `$lookup`, `$graphLookup`, `$unionWith` or `populate`.
+    pub via: String,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
 pub enum IndexSuggestion {
     SingleField { field: String, count: usize },
     Compound { fields: Vec<String> },
@@ -46,80 +278,235 @@ impl fmt::Display for IndexSuggestion {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct QueryPattern {
+    /// Stable ID of the [`QueryShape`] shared by every query in the pattern.
+    pub shape_id: String,
+    /// The shape, e.g. `users.find({email:$eq}).sort({createdAt:-1})`.
+    pub shape: String,
     pub fields: Vec<String>,
     pub count: usize,
     pub queries: Vec<MongoQuery>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct FileAnalysis {
     pub file_path: String,
+    pub category: FileCategory,
     pub patterns: Vec<QueryPattern>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CollectionAnalysis {
     pub collection: String,
     pub files: Vec<FileAnalysis>,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldWriters {
+    pub field: String,
+    pub writers: Vec<MongoQuery>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionWrites {
+    pub collection: String,
+    pub fields: Vec<FieldWriters>,
+}
+
+/// Everything read from a project's source files.
+#[derive(Debug, Default)]
+pub struct ProjectAnalysis {
+    /// Ordered by file, line and method.
+    pub queries: Vec<MongoQuery>,
+    pub suppressions: Vec<Suppression>,
+    /// Files that could not be parsed, ordered by file.
+    pub parse_errors: Vec<ParseError>,
+    /// Unchanged files left out of a [`ScanOptions::changed`] scan because the
+    /// cache had no results for them.
+    pub uncached_files: usize,
+}
+
+impl ProjectAnalysis {
+    /// Combines per-file results, ordering queries by file, line and method.
+    pub fn from_files(files: impl IntoIterator<Item = ParsedFile>) -> Self {
+        let mut project = Self::default();
+        for parsed in files {
+            project.queries.extend(parsed.queries);
+            project.suppressions.extend(parsed.suppressions);
+            project.parse_errors.extend(parsed.parse_errors);
+        }
+        project.parse_errors.sort_by(|a, b| a.file.cmp(&b.file));
+
+        project.queries.sort_by(|a, b| {
+            a.file
+                .cmp(&b.file)
+                .then(a.line.cmp(&b.line))
+                .then(a.method.cmp(&b.method))
+        });
+        project.queries.dedup();
+        project
+    }
+}
+
 pub fn analyze_project(root_dir: &Path) -> Result<Vec<MongoQuery>, Box<dyn std::error::Error>> {
-    let ts_files = find_ts_files(root_dir);
+    scan_project(root_dir).map(|project| project.queries)
+}
 
-    let queries: Vec<MongoQuery> = ts_files
-        .into_iter()
-        .filter_map(|path| match fs::read_to_string(&path) {
-            Ok(content) => {
-                let path_str = path.to_string_lossy();
-                Some(find_mongo_queries(&content, &path_str))
+/// How [`scan_project_with`] reads a project.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanOptions {
+    /// Number of files parsed at once.
+    pub jobs: usize,
+    /// Reuse and update the [`AnalysisCache`] in `.redshift-cache` under the
+    /// root, so unchanged files are not parsed again.
+    pub cache: bool,
+    /// Which files to analyze, usually the `files` section of the config.
+    pub files: FilesConfig,
+    /// With `cache`, parse only these files, relative to the root, and take
+    /// the others from the cache. Unchanged files it has no results for are
+    /// left out and counted in [`ProjectAnalysis::uncached_files`].
+    pub changed: Option<BTreeSet<String>>,
+}
+
+impl Default for ScanOptions {
+    /// One job per available CPU, without a cache.
+    fn default() -> Self {
+        Self {
+            jobs: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            cache: false,
+            files: FilesConfig::default(),
+            changed: None,
+        }
+    }
+}
+
+/// Like [`analyze_project`], but also collects inline suppression comments.
+pub fn scan_project(root_dir: &Path) -> Result<ProjectAnalysis, Box<dyn std::error::Error>> {
+    scan_project_with(root_dir, &ScanOptions::default())
+}
+
+pub fn scan_project_with(
+    root_dir: &Path,
+    options: &ScanOptions,
+) -> Result<ProjectAnalysis, Box<dyn std::error::Error>> {
+    let source_files = find_source_files(root_dir, &options.files);
+    let cache = options.cache.then(|| AnalysisCache::load(root_dir));
+
+    // Without a cache to take them from, unchanged files are parsed as well.
+    let changed = options.changed.as_ref().filter(|_| cache.is_some());
+    let (to_parse, unchanged): (Vec<&SourceFile>, Vec<&SourceFile>) =
+        source_files.iter().partition(|source| {
+            changed.is_none_or(|changed| {
+                changed.contains(&relative_path(root_dir, &source.path.to_string_lossy()))
+            })
+        });
+    let ts_files: Vec<PathBuf> = to_parse.iter().map(|file| file.path.clone()).collect();
+    let parsed_files = parse_files(&ts_files, options.jobs, cache.as_ref());
+
+    // The new cache holds only files seen in this scan, so deleted files drop out.
+    let mut updated_cache = AnalysisCache::new(root_dir);
+    let mut files = Vec::new();
+    let mut uncached_files = 0;
+    let cached_files = unchanged.iter().map(|source| {
+        fs::read_to_string(&source.path).map(|content| {
+            let hash = content_hash(&content);
+            let parsed = cache
+                .as_ref()
+                .and_then(|cache| cache.get(&source.path.to_string_lossy(), &hash));
+            parsed.map(|parsed| (parsed, hash))
+        })
+    });
+    let results: Vec<_> = to_parse
+        .iter()
+        .zip(parsed_files.into_iter().map(|parsed| parsed.map(Some)))
+        .chain(unchanged.iter().zip(cached_files))
+        .collect();
+    for (source, parsed) in results {
+        let path = &source.path;
+        match parsed {
+            Ok(Some((mut parsed, hash))) => {
+                updated_cache.insert(&path.to_string_lossy(), hash, &parsed);
+                parsed.set_category(source.category);
+                files.push(parsed);
             }
+            Ok(None) => uncached_files += 1,
             Err(e) => {
                 eprintln!("Warning: Failed to read file {}: {}", path.display(), e);
-                None
             }
-        })
-        .flatten()
-        .collect();
+        }
+    }
 
-    let mut queries = queries;
-    queries.sort_by(|a, b| {
-        a.file
-            .cmp(&b.file)
-            .then(a.line.cmp(&b.line))
-            .then(a.method.cmp(&b.method))
-    });
-    queries.dedup();
+    if cache.is_some() {
+        if let Err(e) = updated_cache.write() {
+            eprintln!(
+                "Warning: Failed to write cache {}: {}",
+                updated_cache.dir().display(),
+                e
+            );
+        }
+    }
 
-    Ok(queries)
+    Ok(ProjectAnalysis {
+        uncached_files,
+        ..ProjectAnalysis::from_files(files)
+    })
 }
 
-fn find_mongo_queries(content: &str, file_path: &str) -> Vec<MongoQuery> {
-    ast_parser::parse_file(content, file_path)
-}
+/// Parses `files` on up to `jobs` threads, returning each file's results and
+/// content hash in the order of `files`. Files whose hash matches `cache` are
+/// not parsed. Workers claim one file at a time and drop its source once
+/// parsed, so at most `jobs` sources are held in memory.
+pub(crate) fn parse_files(
+    files: &[PathBuf],
+    jobs: usize,
+    cache: Option<&AnalysisCache>,
+) -> Vec<io::Result<(ParsedFile, String)>> {
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<Option<io::Result<(ParsedFile, String)>>> =
+        files.iter().map(|_| None).collect();
 
-fn find_ts_files(dir: &Path) -> Vec<PathBuf> {
-    const IGNORED_DIRS: &[&str] = &["node_modules", ".git", "dist", "target"];
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.clamp(1, files.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut parsed = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(path) = files.get(index) else {
+                            break;
+                        };
+                        let result = fs::read_to_string(path).map(|content| {
+                            let path = path.to_string_lossy();
+                            let hash = content_hash(&content);
+                            let parsed = cache
+                                .and_then(|cache| cache.get(&path, &hash))
+                                .unwrap_or_else(|| ast_parser::parse_source(&content, &path));
+                            (parsed, hash)
+                        });
+                        parsed.push((index, result));
+                    }
+                    parsed
+                })
+            })
+            .collect();
 
-    WalkDir::new(dir)
-        .into_iter()
-        .filter_entry(|e| {
-            let file_name = e.file_name().to_str().unwrap_or("");
-            !IGNORED_DIRS.contains(&file_name)
-        })
-        .filter_map(|e| e.ok())
-        .filter(|e| {
-            e.path()
-                .extension()
-                .map_or(false, |ext| ext == "ts" || ext == "tsx")
-        })
-        .filter(|e| {
-            let path_str = e.path().to_string_lossy();
-            !path_str.ends_with(".spec.ts") && !path_str.ends_with(".test.ts")
-        })
-        .map(|e| e.path().to_path_buf())
-        .collect()
+        for worker in workers {
+            let parsed = worker
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            for (index, result) in parsed {
+                results[index] = Some(result);
+            }
+        }
+    });
+
+    results.into_iter().flatten().collect()
 }
 
 pub fn get_indexes(queries: &[MongoQuery]) -> HashMap<String, Vec<IndexSuggestion>> {
@@ -155,7 +542,7 @@ fn generate_suggestions_for_collection(
     let mut suggestions = Vec::new();
 
     let mut sorted_fields: Vec<_> = field_usage.iter().collect();
-    sorted_fields.sort_by(|a, b| b.1.cmp(a.1));
+    sorted_fields.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
 
     if let Some((field, count)) = sorted_fields.first() {
         if **count > 2 {
@@ -179,21 +566,16 @@ fn generate_suggestions_for_collection(
 }
 
 pub fn get_collection_analysis(queries: &[MongoQuery]) -> Vec<CollectionAnalysis> {
-    // Collection -> File -> Pattern(joined string) -> List of queries
-    let mut data: HashMap<String, HashMap<String, HashMap<String, Vec<MongoQuery>>>> =
+    // Collection -> File -> Shape -> List of queries
+    let mut data: HashMap<String, HashMap<String, HashMap<QueryShape, Vec<MongoQuery>>>> =
         HashMap::new();
 
     for query in queries {
-        let files_map = data.entry(query.collection.clone()).or_default();
-        let patterns_map = files_map.entry(query.file.clone()).or_default();
-
-        // Create a sorted key for the pattern to identify identical sets of fields
-        let mut fields = query.query_fields.clone();
-        fields.sort();
-        let pattern_key = fields.join(","); // Empty string for no fields
-
-        patterns_map
-            .entry(pattern_key)
+        data.entry(query.collection.clone())
+            .or_default()
+            .entry(query.file.clone())
+            .or_default()
+            .entry(QueryShape::of(query))
             .or_default()
             .push(query.clone());
     }
@@ -205,28 +587,32 @@ pub fn get_collection_analysis(queries: &[MongoQuery]) -> Vec<CollectionAnalysis
             let mut files: Vec<FileAnalysis> = files_map
                 .into_iter()
                 .map(|(file_path, patterns_map)| {
+                    let category = patterns_map
+                        .values()
+                        .next()
+                        .map_or(FileCategory::Source, |queries| queries[0].category);
                     let mut patterns: Vec<QueryPattern> = patterns_map
                         .into_iter()
-                        .map(|(key, queries)| {
-                            let fields = if key.is_empty() {
-                                Vec::new()
-                            } else {
-                                key.split(',').map(|s| s.to_string()).collect()
-                            };
-                            let count = queries.len();
+                        .map(|(shape, queries)| {
+                            let mut fields: Vec<String> = queries[0].query_fields.clone();
+                            fields.sort();
+                            fields.dedup();
                             QueryPattern {
+                                shape_id: shape.id(),
+                                shape: shape.to_string(),
                                 fields,
-                                count,
+                                count: queries.len(),
                                 queries,
                             }
                         })
                         .collect();
 
-                    // Sort patterns by count (descending)
-                    patterns.sort_by(|a, b| b.count.cmp(&a.count));
+                    // Sort patterns by count (descending), then by shape for stable output
+                    patterns.sort_by(|a, b| b.count.cmp(&a.count).then(a.shape.cmp(&b.shape)));
 
                     FileAnalysis {
                         file_path,
+                        category,
                         patterns,
                     }
                 })
@@ -245,6 +631,92 @@ pub fn get_collection_analysis(queries: &[MongoQuery]) -> Vec<CollectionAnalysis
     result
 }
 
+pub fn get_write_analysis(queries: &[MongoQuery]) -> Vec<CollectionWrites> {
+    // Collection -> written field -> queries writing it
+    let mut data: HashMap<String, HashMap<String, Vec<MongoQuery>>> = HashMap::new();
+
+    for query in queries {
+        for field in &query.written_fields {
+            data.entry(query.collection.clone())
+                .or_default()
+                .entry(field.clone())
+                .or_default()
+                .push(query.clone());
+        }
+    }
+
+    let mut result: Vec<CollectionWrites> = data
+        .into_iter()
+        .map(|(collection, fields_map)| {
+            let mut fields: Vec<FieldWriters> = fields_map
+                .into_iter()
+                .map(|(field, mut writers)| {
+                    writers.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+                    FieldWriters { field, writers }
+                })
+                .collect();
+            fields.sort_by(|a, b| a.field.cmp(&b.field));
+            CollectionWrites { collection, fields }
+        })
+        .collect();
+
+    result.sort_by(|a, b| a.collection.cmp(&b.collection));
+
+    result
+}
+
+/// How many filtered query sites of a configured collection can use one of
+/// its configured indexes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexCoverage {
+    pub collection: String,
+    /// Query sites with at least one filter field.
+    pub queries: usize,
+    /// Query sites whose filter can use a configured index.
+    pub supported: usize,
+    /// Distinct field sets that no configured index supports.
+    pub unsupported_patterns: Vec<Vec<String>>,
+}
+
+pub fn get_index_coverage(queries: &[MongoQuery], config: &AnalyzerConfig) -> Vec<IndexCoverage> {
+    let mut result: Vec<IndexCoverage> = config
+        .collections
+        .iter()
+        .map(|collection_config| {
+            let mut coverage = IndexCoverage {
+                collection: collection_config.name.clone(),
+                queries: 0,
+                supported: 0,
+                unsupported_patterns: Vec::new(),
+            };
+            for query in queries {
+                if query.collection != collection_config.name || query.query_fields.is_empty() {
+                    continue;
+                }
+                coverage.queries += 1;
+                if collection_config
+                    .indexes
+                    .iter()
+                    .any(|index| index.supports(&query.query_fields))
+                {
+                    coverage.supported += 1;
+                } else if !coverage.unsupported_patterns.contains(&query.query_fields) {
+                    coverage
+                        .unsupported_patterns
+                        .push(query.query_fields.clone());
+                }
+            }
+            coverage.unsupported_patterns.sort();
+            coverage
+        })
+        .collect();
+
+    result.sort_by(|a, b| a.collection.cmp(&b.collection));
+
+    result
+}
+
 pub fn get_config_warnings(queries: &[MongoQuery], config: &AnalyzerConfig) -> Vec<ConfigWarning> {
     let mut warnings = Vec::new();
 
@@ -266,18 +738,23 @@ pub fn get_config_warnings(queries: &[MongoQuery], config: &AnalyzerConfig) -> V
     let mut unknown_collections: Vec<_> = unknown_collection_counts.into_iter().collect();
     unknown_collections.sort_by(|a, b| a.0.cmp(b.0));
     for (collection, count) in unknown_collections {
-        warnings.push(ConfigWarning {
-            severity: config.defaults.unknown_collection_severity(),
-            message: format!(
+        warnings.push(ConfigWarning::project(
+            Rule::UnknownCollection,
+            config.defaults.unknown_collection_severity(),
+            collection,
+            format!(
                 "Collection '{}' is queried {} times but is not configured",
                 collection, count
             ),
-            file: None,
-            line: None,
-        });
+        ));
     }
 
     for query in queries {
+        // Inserts carry documents rather than predicates, and the operations of a
+        // bulkWrite are checked individually.
+        if query.operation == OperationClass::Insert || query.method == "bulkWrite" {
+            continue;
+        }
         let Some(collection_config) = collections_by_name.get(query.collection.as_str()) else {
             continue;
         };
@@ -302,20 +779,69 @@ pub fn get_config_warnings(queries: &[MongoQuery], config: &AnalyzerConfig) -> V
             let severity = guidance
                 .severity
                 .unwrap_or(config.defaults.recommended_predicate_miss_severity());
-            warnings.push(ConfigWarning {
+            warnings.push(ConfigWarning::at_query(
+                Rule::RecommendedPredicateMiss,
                 severity,
-                message: format!(
+                format!(
                     "Query on collection '{}' with method '{}' should include one of [{}] in predicate",
                     query.collection,
                     query.method,
                     recommended.join(", ")
                 ),
-                file: Some(query.file.clone()),
-                line: Some(query.line),
-            });
+                query,
+            ));
+        }
+    }
+
+    for query in queries {
+        if !query.returns_full_document() {
+            continue;
+        }
+        let Some(collection_config) = collections_by_name.get(query.collection.as_str()) else {
+            continue;
+        };
+        let Some(rule) = collection_config
+            .performance
+            .as_ref()
+            .and_then(|performance| performance.full_document_read.as_ref())
+        else {
+            continue;
+        };
+
+        let mut message = format!(
+            "Query on collection '{}' with method '{}' returns full documents; add a projection",
+            query.collection, query.method
+        );
+        let index_only = Projection {
+            include: query.query_fields.clone(),
+            exclude: vec!["_id".to_string()],
+        };
+        if let Some(index) = collection_config
+            .indexes
+            .iter()
+            .find(|index| index.covers(&query.query_fields, &index_only))
+        {
+            message.push_str(&format!(
+                " (projecting only keys of index '{}' without _id would make it a covered query)",
+                index.label()
+            ));
         }
+
+        warnings.push(ConfigWarning::at_query(
+            Rule::FullDocumentRead,
+            rule.severity.unwrap_or_default(),
+            message,
+            query,
+        ));
     }
 
+    sort_warnings(&mut warnings);
+    warnings
+}
+
+/// Orders findings by severity, then file, line and message, as every
+/// report lists them.
+pub(crate) fn sort_warnings(warnings: &mut [ConfigWarning]) {
     warnings.sort_by(|a, b| {
         severity_rank(a.severity)
             .cmp(&severity_rank(b.severity))
@@ -323,8 +849,33 @@ pub fn get_config_warnings(queries: &[MongoQuery], config: &AnalyzerConfig) -> V
             .then(a.line.cmp(&b.line))
             .then(a.message.cmp(&b.message))
     });
+}
 
-    warnings
+/// Path of `file` relative to the analyzed `root`, with `/` separators, or
+/// `file` unchanged when it is not under `root`.
+pub fn relative_path(root: &Path, file: &str) -> String {
+    let path = Path::new(file);
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `path` with every byte other than `/` and the URI unreserved characters
+/// percent-encoded, so it can be used in a URI.
+pub fn percent_encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
 }
 
 fn severity_rank(severity: Severity) -> usize {
@@ -335,11 +886,126 @@ fn severity_rank(severity: Severity) -> usize {
     }
 }
 
+/// A scratch directory for one test, named after it and the process so tests
+/// running in parallel or concurrently never share one. Removed on drop.
+#[cfg(test)]
+pub(crate) struct TestDir(PathBuf);
+
+#[cfg(test)]
+impl TestDir {
+    pub(crate) fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("redshift-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Writes `content` to `relative`, creating parent directories.
+    pub(crate) fn write(&self, relative: &str, content: &str) -> PathBuf {
+        let path = self.0.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, content).unwrap();
+        path
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeSet;
+    use std::path::Path;
+
     use crate::config::AnalyzerConfig;
 
-    use super::{get_config_warnings, MongoQuery};
+    use super::{
+        get_config_warnings, get_index_coverage, get_write_analysis, scan_project_with, MongoQuery,
+        OperationClass, ProjectAnalysis, Projection, ScanOptions, TestDir,
+    };
+
+    #[test]
+    fn parallel_scan_matches_sequential_scan() {
+        let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        let sequential = scan_project_with(
+            &examples,
+            &ScanOptions {
+                jobs: 1,
+                ..ScanOptions::default()
+            },
+        )
+        .unwrap();
+        let parallel = scan_project_with(
+            &examples,
+            &ScanOptions {
+                jobs: 8,
+                ..ScanOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert!(!sequential.queries.is_empty());
+        assert_eq!(sequential.queries, parallel.queries);
+        assert_eq!(sequential.suppressions, parallel.suppressions);
+    }
+
+    #[test]
+    fn changed_scan_parses_changed_files_and_reuses_cached_ones() {
+        let dir = TestDir::new("changed-scan");
+        dir.write(
+            "models/user.ts",
+            "export const User = model('User', schema);",
+        );
+        dir.write(
+            "src/users.ts",
+            "import { User } from '../models/user';\nUser.find({ email });",
+        );
+        dir.write("src/orders.ts", "db.collection('orders').find({ status });");
+        let changed = ScanOptions {
+            cache: true,
+            changed: Some(BTreeSet::from(["src/users.ts".to_string()])),
+            ..ScanOptions::default()
+        };
+        let collections = |project: &ProjectAnalysis| -> Vec<String> {
+            project
+                .queries
+                .iter()
+                .map(|query| query.collection.clone())
+                .collect()
+        };
+
+        let cold = scan_project_with(dir.path(), &changed).unwrap();
+        assert_eq!(collections(&cold), vec!["User"]);
+        assert_eq!(cold.uncached_files, 2);
+
+        let full = ScanOptions {
+            cache: true,
+            ..ScanOptions::default()
+        };
+        scan_project_with(dir.path(), &full).unwrap();
+        let warm = scan_project_with(dir.path(), &changed).unwrap();
+        assert_eq!(collections(&warm), vec!["orders", "User"]);
+        assert_eq!(warm.uncached_files, 0);
+
+        let uncached = scan_project_with(
+            dir.path(),
+            &ScanOptions {
+                cache: false,
+                ..changed
+            },
+        )
+        .unwrap();
+        assert_eq!(collections(&uncached), vec!["orders", "User"]);
+        assert_eq!(uncached.uncached_files, 0);
+    }
 
     #[test]
     fn emits_unknown_collection_warning() {
@@ -357,6 +1023,7 @@ mod tests {
             method: "find".to_string(),
             query_fields: vec!["organizationId".to_string()],
             raw_match: "orders.find({ organizationId })".to_string(),
+            ..Default::default()
         }];
 
         let warnings = get_config_warnings(&queries, &config);
@@ -385,6 +1052,7 @@ mod tests {
             method: "find".to_string(),
             query_fields: vec!["email".to_string()],
             raw_match: "users.find({ email })".to_string(),
+            ..Default::default()
         }];
 
         let warnings = get_config_warnings(&queries, &config);
@@ -416,10 +1084,149 @@ mod tests {
             method: "find".to_string(),
             query_fields: vec!["organizationId".to_string()],
             raw_match: "users.find({ organizationId })".to_string(),
+            ..Default::default()
         }];
 
         let warnings = get_config_warnings(&queries, &config);
         assert_eq!(warnings.len(), 1);
         assert!(warnings[0].message.contains("email"));
     }
+
+    #[test]
+    fn inserts_are_exempt_from_predicate_guidance() {
+        let config: AnalyzerConfig = serde_json::from_str(
+            r#"{
+                "collections": [{
+                    "name": "users",
+                    "predicateGuidance": {
+                        "recommendedFields": ["organizationId"]
+                    }
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        let queries = vec![MongoQuery {
+            file: "src/user.service.ts".to_string(),
+            line: 25,
+            collection: "users".to_string(),
+            method: "insertOne".to_string(),
+            operation: OperationClass::Insert,
+            raw_match: "users.insertOne(user)".to_string(),
+            ..Default::default()
+        }];
+
+        let warnings = get_config_warnings(&queries, &config);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn groups_written_fields_by_collection() {
+        let queries = vec![
+            MongoQuery {
+                file: "src/user.service.ts".to_string(),
+                line: 30,
+                collection: "users".to_string(),
+                method: "updateOne".to_string(),
+                operation: OperationClass::Write,
+                written_fields: vec!["lastLogin".to_string(), "status".to_string()],
+                ..Default::default()
+            },
+            MongoQuery {
+                file: "src/admin.service.ts".to_string(),
+                line: 12,
+                collection: "users".to_string(),
+                method: "updateMany".to_string(),
+                operation: OperationClass::Write,
+                written_fields: vec!["status".to_string()],
+                ..Default::default()
+            },
+        ];
+
+        let writes = get_write_analysis(&queries);
+        assert_eq!(writes.len(), 1);
+        let fields: Vec<_> = writes[0]
+            .fields
+            .iter()
+            .map(|f| (f.field.as_str(), f.writers.len()))
+            .collect();
+        assert_eq!(fields, vec![("lastLogin", 1), ("status", 2)]);
+        assert_eq!(writes[0].fields[1].writers[0].file, "src/admin.service.ts");
+    }
+
+    #[test]
+    fn full_document_read_warning_points_at_covering_index() {
+        let config: AnalyzerConfig = serde_json::from_str(
+            r#"{
+                "collections": [{
+                    "name": "users",
+                    "indexes": [{
+                        "name": "users_email",
+                        "keys": [{ "field": "email", "order": 1 }]
+                    }],
+                    "performance": {
+                        "fullDocumentRead": { "severity": "info" }
+                    }
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        let full = MongoQuery {
+            file: "src/user.service.ts".to_string(),
+            line: 40,
+            collection: "users".to_string(),
+            method: "findOne".to_string(),
+            query_fields: vec!["email".to_string()],
+            ..Default::default()
+        };
+        let projected = MongoQuery {
+            line: 41,
+            projection: Some(Projection {
+                include: vec!["email".to_string()],
+                exclude: vec!["_id".to_string()],
+            }),
+            ..full.clone()
+        };
+
+        let warnings = get_config_warnings(&[full, projected], &config);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, Some(40));
+        assert!(warnings[0].message.contains("users_email"));
+    }
+
+    #[test]
+    fn index_coverage_counts_supported_filters() {
+        let config: AnalyzerConfig = serde_json::from_str(
+            r#"{
+                "collections": [{
+                    "name": "users",
+                    "indexes": [{ "keys": [{ "field": "email", "order": 1 }] }]
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        let query = |fields: &[&str]| MongoQuery {
+            collection: "users".to_string(),
+            method: "find".to_string(),
+            query_fields: fields.iter().map(|f| f.to_string()).collect(),
+            ..Default::default()
+        };
+        let queries = vec![
+            query(&["email", "status"]),
+            query(&["status"]),
+            query(&["status"]),
+            query(&[]),
+        ];
+
+        let coverage = get_index_coverage(&queries, &config);
+        assert_eq!(coverage.len(), 1);
+        assert_eq!(coverage[0].queries, 3);
+        assert_eq!(coverage[0].supported, 1);
+        assert_eq!(
+            coverage[0].unsupported_patterns,
+            vec![vec!["status".to_string()]]
+        );
+    }
 }
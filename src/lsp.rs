@@ -0,0 +1,561 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::config::{load_project_config, ConfigWarning, LoadedConfig, Severity, CONFIG_FILE_NAME};
+use crate::report::AnalysisReport;
+use crate::shape::QueryShape;
+use crate::watch::ProjectModel;
+use crate::{percent_encode_path, MongoQuery, ScanOptions};
+
+/// JSON-RPC error code for requests the server does not implement.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// A Language Server Protocol server publishing findings as diagnostics,
+/// describing queries on hover, and counting sites that share a query's
+/// shape in code lenses.
+///
+/// The whole workspace is parsed once on `initialized`; after that, an open
+/// document is re-parsed from the editor's text whenever it changes, and
+/// other files from disk when a document is saved. The config is read again
+/// only when `redshift.config.json` is saved.
+pub struct LanguageServer {
+    root: PathBuf,
+    options: ScanOptions,
+    model: Option<ProjectModel>,
+    config: Option<LoadedConfig>,
+    report: Option<AnalysisReport>,
+    exit: bool,
+}
+
+impl LanguageServer {
+    /// A server for `root`, used unless the client sends a `rootUri`.
+    pub fn new(root: &Path, options: ScanOptions) -> Self {
+        Self {
+            // Absolute, so file paths compare equal to those in document URIs.
+            root: fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf()),
+            options,
+            model: None,
+            config: None,
+            report: None,
+            exit: false,
+        }
+    }
+
+    /// Whether the client sent `exit`.
+    pub fn exited(&self) -> bool {
+        self.exit
+    }
+
+    /// Handles one request or notification, returning the messages to send
+    /// back: at most one response, plus any notifications.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let id = message.get("id").cloned();
+
+        let mut outgoing = Vec::new();
+        let result = match method {
+            "initialize" => {
+                if let Some(root) = params["rootUri"].as_str().and_then(uri_to_path) {
+                    self.root = root;
+                }
+                Some(json!({
+                    "capabilities": {
+                        // The parser counts columns in UTF-16 code units, as
+                        // LSP does by default.
+                        "positionEncoding": "utf-16",
+                        "textDocumentSync": { "openClose": true, "change": 1, "save": true },
+                        "hoverProvider": true,
+                        "codeLensProvider": { "resolveProvider": false }
+                    },
+                    "serverInfo": { "name": "redshift", "version": env!("CARGO_PKG_VERSION") }
+                }))
+            }
+            "initialized" => {
+                outgoing.extend(self.reload_config());
+                if let Some(loaded) = &self.config {
+                    self.options.files = loaded.config.files.clone();
+                }
+                self.model = Some(ProjectModel::load(&self.root, &self.options));
+                outgoing.extend(self.reanalyze());
+                None
+            }
+            "textDocument/didOpen" => {
+                let document = &params["textDocument"];
+                if let Some(path) = document["uri"].as_str().and_then(uri_to_path) {
+                    let text = document["text"].as_str().unwrap_or_default();
+                    if let Some(model) = &mut self.model {
+                        model.set_document(path, text.to_string());
+                    }
+                    outgoing.extend(self.reanalyze());
+                }
+                None
+            }
+            "textDocument/didChange" => {
+                // Full sync: the last change holds the whole document.
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                if let (Some(path), Some(text)) = (
+                    params["textDocument"]["uri"].as_str().and_then(uri_to_path),
+                    text,
+                ) {
+                    if let Some(model) = &mut self.model {
+                        model.set_document(path, text.to_string());
+                    }
+                    outgoing.extend(self.reanalyze());
+                }
+                None
+            }
+            "textDocument/didSave" => {
+                let saved = params["textDocument"]["uri"].as_str().and_then(uri_to_path);
+                if saved.is_some_and(|path| path.file_name() == Some(CONFIG_FILE_NAME.as_ref())) {
+                    outgoing.extend(self.reload_config());
+                    if let (Some(model), Some(loaded)) = (&mut self.model, &self.config) {
+                        model.set_selection(loaded.config.files.clone());
+                    }
+                }
+                if let Some(model) = &mut self.model {
+                    model.refresh();
+                }
+                outgoing.extend(self.reanalyze());
+                None
+            }
+            "textDocument/didClose" => {
+                if let Some(uri) = params["textDocument"]["uri"].as_str() {
+                    if let Some(path) = uri_to_path(uri) {
+                        if let Some(model) = &mut self.model {
+                            model.close_document(&path);
+                        }
+                    }
+                    outgoing.push(publish_diagnostics(uri, Vec::new()));
+                    outgoing.extend(self.reanalyze());
+                }
+                None
+            }
+            "textDocument/hover" => Some(self.hover(params)),
+            "textDocument/codeLens" => Some(self.code_lenses(params)),
+            "shutdown" => Some(Value::Null),
+            "exit" => {
+                self.exit = true;
+                None
+            }
+            _ => {
+                if let Some(id) = id {
+                    outgoing.push(json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": {
+                            "code": METHOD_NOT_FOUND,
+                            "message": format!("Unsupported method {}", method)
+                        }
+                    }));
+                }
+                return outgoing;
+            }
+        };
+
+        if let (Some(id), Some(result)) = (id, result) {
+            outgoing.insert(0, json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+        }
+        outgoing
+    }
+
+    /// Reads `redshift.config.json`, keeping the previous config and telling
+    /// the user if it is invalid.
+    fn reload_config(&mut self) -> Option<Value> {
+        match load_project_config(&self.root) {
+            Ok(config) => {
+                self.config = config;
+                None
+            }
+            Err(err) => Some(json!({
+                "jsonrpc": "2.0",
+                "method": "window/showMessage",
+                "params": { "type": 1, "message": format!("redshift: {}", err) }
+            })),
+        }
+    }
+
+    /// Runs the rules over the project model and publishes the findings of
+    /// every open document.
+    fn reanalyze(&mut self) -> Vec<Value> {
+        let Some(model) = &self.model else {
+            return Vec::new();
+        };
+        let project = model.project();
+        let mut report = AnalysisReport::new(&self.root, project.queries, self.config.as_ref());
+        report.add_parse_errors(&project.parse_errors);
+        report.apply_suppressions(&project.suppressions);
+
+        let mut outgoing = Vec::new();
+        for path in model.documents() {
+            let file = path.to_string_lossy();
+            let diagnostics = report
+                .warnings
+                .iter()
+                .filter(|warning| warning.file.as_deref() == Some(file.as_ref()))
+                .map(diagnostic)
+                .collect();
+            outgoing.push(publish_diagnostics(&path_to_uri(path), diagnostics));
+        }
+        self.report = Some(report);
+        outgoing
+    }
+
+    /// The innermost query in the document at the given position.
+    fn query_at(&self, params: &Value) -> Option<&MongoQuery> {
+        let report = self.report.as_ref()?;
+        let file = uri_to_path(params["textDocument"]["uri"].as_str()?)?;
+        let file = file.to_string_lossy();
+        let line = params["position"]["line"].as_u64()? as usize + 1;
+        let column = params["position"]["character"].as_u64()? as usize + 1;
+
+        report
+            .queries
+            .iter()
+            .filter(|query| {
+                query.file == file
+                    && (query.line, query.column) <= (line, column)
+                    && (line, column) < (query.end_line, query.end_column)
+            })
+            .max_by_key(|query| (query.line, query.column))
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let (Some(query), Some(report)) = (self.query_at(params), &self.report) else {
+            return Value::Null;
+        };
+        let shape = QueryShape::of(query);
+        let mut text = format!(
+            "**{}** · `{}` ({})\n\n`{}`\n\n",
+            query.collection, query.method, query.operation, shape
+        );
+
+        let collection_config = self.config.as_ref().and_then(|loaded| {
+            loaded
+                .config
+                .collections
+                .iter()
+                .find(|collection| collection.name == query.collection)
+        });
+        match collection_config {
+            None => text.push_str("Collection not configured in `redshift.config.json`.\n"),
+            Some(collection) => {
+                let indexes: Vec<String> = collection
+                    .indexes
+                    .iter()
+                    .filter(|index| index.supports(&query.query_fields))
+                    .map(|index| format!("`{}`", index.label()))
+                    .collect();
+                if indexes.is_empty() {
+                    text.push_str("No configured index supports this filter.\n");
+                } else {
+                    text.push_str(&format!("Indexes: {}\n", indexes.join(", ")));
+                }
+            }
+        }
+
+        let suggestions: Vec<String> = report
+            .index_suggestions
+            .iter()
+            .filter(|collection| collection.collection == query.collection)
+            .flat_map(|collection| &collection.suggestions)
+            .map(|suggestion| format!("- {}", suggestion))
+            .collect();
+        if !suggestions.is_empty() {
+            text.push_str(&format!("\nSuggestions:\n{}\n", suggestions.join("\n")));
+        }
+
+        json!({
+            "contents": { "kind": "markdown", "value": text },
+            "range": range(query.line, query.column, query.end_line, query.end_column)
+        })
+    }
+
+    fn code_lenses(&self, params: &Value) -> Value {
+        let (Some(report), Some(path)) = (
+            &self.report,
+            params["textDocument"]["uri"].as_str().and_then(uri_to_path),
+        ) else {
+            return json!([]);
+        };
+        let file = path.to_string_lossy();
+
+        let mut sites: BTreeMap<String, usize> = BTreeMap::new();
+        for query in &report.queries {
+            *sites.entry(QueryShape::of(query).id()).or_default() += 1;
+        }
+
+        let lenses: Vec<Value> = report
+            .queries
+            .iter()
+            .filter(|query| query.file == file)
+            .map(|query| {
+                let others = sites[&QueryShape::of(query).id()] - 1;
+                let title = match others {
+                    0 => "no other site shares this shape".to_string(),
+                    1 => "1 other site shares this shape".to_string(),
+                    n => format!("{} other sites share this shape", n),
+                };
+                json!({
+                    "range": range(query.line, query.column, query.line, query.column),
+                    "command": { "title": title, "command": "" }
+                })
+            })
+            .collect();
+        Value::Array(lenses)
+    }
+}
+
+/// Serves `server` over `input` and `output` until the client exits or
+/// closes the stream.
+pub fn run(
+    server: &mut LanguageServer,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> io::Result<()> {
+    while let Some(message) = read_message(&mut input)? {
+        for reply in server.handle(&message) {
+            write_message(&mut output, &reply)?;
+        }
+        if server.exited() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Reads one `Content-Length`-framed message, or `None` at end of input.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = length
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "Missing Content-Length header"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics }
+    })
+}
+
+fn diagnostic(warning: &ConfigWarning) -> Value {
+    let line = warning.line.unwrap_or(1);
+    let column = warning.column.unwrap_or(1);
+    json!({
+        "range": range(
+            line,
+            column,
+            warning.end_line.unwrap_or(line),
+            warning.end_column.unwrap_or(column + 1),
+        ),
+        "severity": match warning.severity {
+            Severity::Error => 1,
+            Severity::Warning => 2,
+            Severity::Info => 3,
+        },
+        "code": warning.rule.id(),
+        "source": "redshift",
+        "message": format!("{}\n{}", warning.message, warning.rule.help())
+    })
+}
+
+/// An LSP range (0-based) from 1-based lines and columns.
+fn range(line: usize, column: usize, end_line: usize, end_column: usize) -> Value {
+    json!({
+        "start": { "line": line.saturating_sub(1), "character": column.saturating_sub(1) },
+        "end": { "line": end_line.saturating_sub(1), "character": end_column.saturating_sub(1) }
+    })
+}
+
+/// The path of a `file://` URI, with percent escapes decoded.
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut index = 0;
+    while index < encoded.len() {
+        let escaped = (encoded[index] == b'%')
+            .then(|| encoded.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(encoded[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok().map(PathBuf::from)
+}
+
+fn path_to_uri(path: &Path) -> String {
+    format!("file://{}", percent_encode_path(&path.to_string_lossy()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{path_to_uri, LanguageServer};
+    use crate::{ScanOptions, TestDir};
+
+    const CONFIG: &str = r#"{ "collections": [{ "name": "users",
+        "indexes": [{ "name": "users_email", "keys": [{ "field": "email", "order": 1 }] }],
+        "predicateGuidance": { "recommendedFields": ["tenantId"] } }] }"#;
+
+    fn start(dir: &TestDir) -> LanguageServer {
+        let mut server = LanguageServer::new(dir.path(), ScanOptions::default());
+        server.handle(&json!({ "id": 1, "method": "initialize", "params": {} }));
+        server.handle(&json!({ "method": "initialized", "params": {} }));
+        server
+    }
+
+    fn open(server: &mut LanguageServer, uri: &str, text: &str) -> Vec<Value> {
+        server.handle(&json!({
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": uri, "text": text } }
+        }))
+    }
+
+    #[test]
+    fn publishes_diagnostics_hovers_and_code_lenses() {
+        let dir = TestDir::new("lsp");
+        dir.write("redshift.config.json", CONFIG);
+        dir.write("other.ts", "db.collection('users').find({ email: 'a' });");
+        let uri = path_to_uri(&dir.path().join("users.ts"));
+
+        let mut server = start(&dir);
+        let published = open(&mut server, &uri, "db.collection('users').find({ email });");
+
+        let diagnostics = &published[0]["params"]["diagnostics"];
+        assert_eq!(published[0]["params"]["uri"], uri);
+        assert_eq!(diagnostics[0]["code"], "recommended-predicate-miss");
+        assert_eq!(diagnostics[0]["range"]["start"]["character"], 0);
+
+        let hover = server.handle(&json!({
+            "id": 2,
+            "method": "textDocument/hover",
+            "params": { "textDocument": { "uri": uri }, "position": { "line": 0, "character": 25 } }
+        }));
+        let text = hover[0]["result"]["contents"]["value"].as_str().unwrap();
+        assert!(text.contains("`users.find({email:$eq})`"));
+        assert!(text.contains("Indexes: `users_email`"));
+
+        let lenses = server.handle(&json!({
+            "id": 3,
+            "method": "textDocument/codeLens",
+            "params": { "textDocument": { "uri": uri } }
+        }));
+        assert_eq!(
+            lenses[0]["result"][0]["command"]["title"],
+            "1 other site shares this shape"
+        );
+    }
+
+    #[test]
+    fn counts_columns_in_utf16_code_units() {
+        let dir = TestDir::new("lsp-encoding");
+        dir.write("redshift.config.json", CONFIG);
+        let uri = path_to_uri(&dir.path().join("users.ts"));
+
+        let mut server = LanguageServer::new(dir.path(), ScanOptions::default());
+        let initialized = server.handle(&json!({ "id": 1, "method": "initialize", "params": {} }));
+        assert_eq!(
+            initialized[0]["result"]["capabilities"]["positionEncoding"],
+            "utf-16"
+        );
+        server.handle(&json!({ "method": "initialized", "params": {} }));
+
+        // The emoji is one character but two UTF-16 code units.
+        let text = "const s = '😀'; db.collection('users').find({ email });";
+        let published = open(&mut server, &uri, text);
+        let position = &published[0]["params"]["diagnostics"][0]["range"]["start"];
+        assert_eq!(position["character"], 16);
+
+        let hover = server.handle(&json!({
+            "id": 2,
+            "method": "textDocument/hover",
+            "params": { "textDocument": { "uri": uri }, "position": { "line": 0, "character": 16 } }
+        }));
+        assert!(hover[0]["result"]["contents"]["value"].is_string());
+    }
+
+    #[test]
+    fn reloads_the_config_only_when_it_is_saved() {
+        let dir = TestDir::new("lsp-config");
+        let config = dir.write("redshift.config.json", CONFIG);
+        let uri = path_to_uri(&dir.path().join("users.ts"));
+
+        let mut server = start(&dir);
+        let text = "db.collection('users').find({ email });";
+        let published = open(&mut server, &uri, text);
+        assert_eq!(
+            published[0]["params"]["diagnostics"]
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+
+        dir.write("redshift.config.json", r#"{ "collections": [] }"#);
+        let published = server.handle(&json!({
+            "method": "textDocument/didChange",
+            "params": { "textDocument": { "uri": uri }, "contentChanges": [{ "text": text }] }
+        }));
+        assert_eq!(
+            published[0]["params"]["diagnostics"]
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let published = server.handle(&json!({
+            "method": "textDocument/didSave",
+            "params": { "textDocument": { "uri": path_to_uri(&config) } }
+        }));
+        assert_eq!(
+            published[0]["params"]["diagnostics"]
+                .as_array()
+                .unwrap()
+                .len(),
+            0
+        );
+    }
+}
@@ -1,7 +1,27 @@
-use clap::{Parser, Subcommand};
-use redshift::config::load_project_config;
-use redshift::{analyze_project, get_collection_analysis, get_config_warnings, get_indexes};
-use std::path::PathBuf;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use redshift::baseline::{Baseline, BASELINE_FILE_NAME};
+use redshift::config::{
+    discover_config, load_project_config, ConfigWarning, LoadedConfig, Severity,
+};
+use redshift::diff::{warning_changes, AnalysisDiff};
+use redshift::git::changed_files;
+use redshift::graph::{CollectionGraph, GraphGrouping};
+use redshift::lsp::{self, LanguageServer};
+use redshift::report::{
+    sorted_index_suggestions, AnalysisReport, CheckstyleReporter, HtmlReporter, IndexReport,
+    JsonReporter, JunitReporter, MarkdownReporter, Reporter, SarifReporter, TextReporter,
+};
+use redshift::shape::{Inventory, LOCK_FILE_NAME};
+use redshift::watch::{stamp, ProjectModel};
+use redshift::{get_write_analysis, relative_path, scan_project_with, ScanOptions};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "redshift")]
@@ -10,100 +30,547 @@ struct Cli {
     #[arg(short, long, default_value = ".")]
     directory: PathBuf,
 
+    /// Number of files to parse in parallel [default: number of CPUs]
+    #[arg(short, long, global = true, value_parser = clap::value_parser!(u16).range(1..))]
+    jobs: Option<u16>,
+
+    /// Parse every file instead of reusing results from .redshift-cache
+    #[arg(long, global = true)]
+    no_cache: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+    Sarif,
+    Junit,
+    Checkstyle,
+    Markdown,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum IndexFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum FailOn {
+    Info,
+    Warning,
+    Error,
+}
+
+impl From<FailOn> for Severity {
+    fn from(fail_on: FailOn) -> Self {
+        match fail_on {
+            FailOn::Info => Severity::Info,
+            FailOn::Warning => Severity::Warning,
+            FailOn::Error => Severity::Error,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum DiffFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum GraphGroupBy {
+    Module,
+    Class,
+}
+
+#[derive(Subcommand)]
+enum BaselineCommand {
+    /// Record every current finding as accepted
+    Write {
+        /// [default: redshift.baseline.json in the analyzed directory]
+        #[arg(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
 #[derive(Subcommand)]
 enum Commands {
     Analyze {
         #[arg(short, long)]
         verbose: bool,
+
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+
+        /// Earlier `--format json` output; markdown then lists only new or changed query shapes
+        #[arg(long, value_name = "REPORT")]
+        previous: Option<PathBuf>,
+
+        /// Exit with status 1 when any finding is at least this severe
+        /// [default: `defaults.failOn` from config, else error]
+        #[arg(long, value_enum)]
+        fail_on: Option<FailOn>,
+
+        /// Report only findings missing from this baseline
+        /// [default path: redshift.baseline.json in the analyzed directory]
+        #[arg(long, value_name = "FILE")]
+        baseline: Option<Option<PathBuf>>,
+
+        /// Report findings only for files changed since this git ref, including
+        /// uncommitted and untracked files; other files come from the cache
+        #[arg(long, value_name = "REF")]
+        changed_since: Option<String>,
+
+        /// Keep running, re-analyzing changed files and printing findings
+        /// that appear or are fixed (text format only)
+        #[arg(long, conflicts_with_all = ["previous", "changed_since"])]
+        watch: bool,
     },
-    Indexes,
+    /// Manage the baseline of accepted findings
+    Baseline {
+        #[command(subcommand)]
+        command: BaselineCommand,
+    },
+    Indexes {
+        #[arg(long, value_enum, default_value_t = IndexFormat::Text)]
+        format: IndexFormat,
+    },
+    /// List which query sites write each field, per collection
+    Writes,
+    /// List query shapes, or record and verify them in redshift.lock.json
+    Inventory {
+        /// Record the current shapes as reviewed
+        #[arg(long, conflicts_with = "check")]
+        write: bool,
+
+        /// Exit with status 1 if a shape is missing from the lockfile
+        #[arg(long)]
+        check: bool,
+
+        /// [default: redshift.lock.json in the analyzed directory]
+        #[arg(long, value_name = "FILE")]
+        lockfile: Option<PathBuf>,
+    },
+    /// Show query shapes, findings and index suggestions that differ between
+    /// two `analyze --format json` reports, or between two checkouts
+    Diff {
+        /// Earlier report, or base checkout directory
+        old: PathBuf,
+
+        /// Later report, or head checkout directory
+        new: PathBuf,
+
+        #[arg(long, value_enum, default_value_t = DiffFormat::Text)]
+        format: DiffFormat,
+    },
+    /// Run a language server on stdio for in-editor diagnostics, hovers and
+    /// code lenses
+    Lsp,
+    /// Print which modules or classes read and write each collection
+    Graph {
+        #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+
+        #[arg(long, value_enum, default_value_t = GraphGroupBy::Class)]
+        group_by: GraphGroupBy,
+    },
+    /// Write a self-contained HTML report
+    Report {
+        #[arg(long, value_name = "FILE")]
+        html: PathBuf,
+    },
+}
+
+/// Findings at or above the `--fail-on` severity were reported.
+const EXIT_FINDINGS: u8 = 1;
+/// `redshift.config.json` could not be read, parsed or validated.
+const EXIT_CONFIG_INVALID: u8 = 3;
+/// Anything else went wrong. (Status 2 is clap's usage error.)
+const EXIT_INTERNAL_ERROR: u8 = 4;
+
+/// Marks an error as coming from the project config, so `main` can exit with
+/// [`EXIT_CONFIG_INVALID`].
+#[derive(Debug)]
+struct ConfigInvalid(Box<dyn Error>);
+
+impl fmt::Display for ConfigInvalid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Error for ConfigInvalid {}
+
+fn load_config(directory: &Path) -> Result<Option<LoadedConfig>, ConfigInvalid> {
+    load_project_config(directory).map_err(ConfigInvalid)
+}
+
+/// `options` with the file selection of `config`, if any.
+fn scan_options(options: &ScanOptions, config: Option<&LoadedConfig>) -> ScanOptions {
+    ScanOptions {
+        files: config
+            .map(|loaded| loaded.config.files.clone())
+            .unwrap_or_default(),
+        ..options.clone()
+    }
+}
+
+/// Analyzes `directory` and applies its inline suppression comments.
+fn build_report(
+    directory: &Path,
+    config: Option<&LoadedConfig>,
+    options: &ScanOptions,
+) -> Result<AnalysisReport, Box<dyn Error>> {
+    let project = scan_project_with(directory, options)?;
+    let mut report = AnalysisReport::new(directory, project.queries, config);
+    report.add_parse_errors(&project.parse_errors);
+    report.apply_suppressions(&project.suppressions);
+    if project.uncached_files > 0 {
+        eprintln!(
+            "Note: {} unchanged file(s) have no cached results, so collection-level findings \
+             are skipped; run analyze without --changed-since once to fill the cache.",
+            project.uncached_files
+        );
+        report.retain_file_findings();
+    }
+    Ok(report)
+}
+
+/// The JSON report for one side of `diff`: read from a file, or produced by
+/// analyzing a directory.
+fn load_analysis(path: &Path, options: &ScanOptions) -> Result<serde_json::Value, Box<dyn Error>> {
+    if path.is_dir() {
+        let loaded_config = load_config(path)?;
+        let options = scan_options(options, loaded_config.as_ref());
+        let report = build_report(path, loaded_config.as_ref(), &options)?;
+        return Ok(serde_json::to_value(&report)?);
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+    serde_json::from_str(&content).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is not a JSON report: {}", path.display(), err),
+        )
+        .into()
+    })
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() -> ExitCode {
     let cli = Cli::parse();
 
-    match cli.command {
-        Commands::Analyze { verbose } => {
-            let results = analyze_project(&cli.directory)?;
-            let analysis = get_collection_analysis(&results);
-            let loaded_config = load_project_config(&cli.directory)?;
+    match run(cli) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            if err.is::<ConfigInvalid>() {
+                ExitCode::from(EXIT_CONFIG_INVALID)
+            } else {
+                ExitCode::from(EXIT_INTERNAL_ERROR)
+            }
+        }
+    }
+}
 
-            for collection_data in &analysis {
-                println!("Collection: {}", collection_data.collection);
+fn run(cli: Cli) -> Result<ExitCode, Box<dyn Error>> {
+    let mut options = ScanOptions {
+        cache: !cli.no_cache,
+        ..ScanOptions::default()
+    };
+    if let Some(jobs) = cli.jobs {
+        options.jobs = usize::from(jobs);
+    }
+    // Every command scans, and which files it scans is configured.
+    let loaded_config = match cli.command {
+        Commands::Diff { .. } | Commands::Lsp => None,
+        _ => load_config(&cli.directory)?,
+    };
+    let options = scan_options(&options, loaded_config.as_ref());
 
-                for file_data in &collection_data.files {
-                    let file_name = std::path::Path::new(&file_data.file_path)
-                        .file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or(&file_data.file_path);
-
-                    println!("  File: {}", file_name);
-
-                    for pattern in &file_data.patterns {
-                        let fields_str = if pattern.fields.is_empty() {
-                            "[no fields]".to_string()
-                        } else {
-                            format!("[{}]", pattern.fields.join(", "))
-                        };
-                        println!("    - {}: {} usages", fields_str, pattern.count);
-
-                        if verbose {
-                            let mut queries = pattern.queries.clone();
-                            queries.sort_by_key(|q| q.line);
-                            for query in queries {
-                                println!("      Line {}: {}", query.line, query.raw_match);
-                            }
-                        }
+    match cli.command {
+        Commands::Analyze {
+            verbose,
+            format,
+            previous,
+            fail_on,
+            baseline,
+            changed_since,
+            watch,
+        } => {
+            let baseline =
+                baseline.map(|path| path.unwrap_or_else(|| cli.directory.join(BASELINE_FILE_NAME)));
+            if watch {
+                if format != Format::Text {
+                    Cli::command()
+                        .error(
+                            clap::error::ErrorKind::ArgumentConflict,
+                            "--watch only supports --format text",
+                        )
+                        .exit();
+                }
+                return watch_project(&cli.directory, &options, verbose, baseline.as_deref());
+            }
+
+            let changed = changed_since
+                .map(|since| changed_files(&cli.directory, &since))
+                .transpose()?;
+            let options = ScanOptions {
+                changed: changed.clone(),
+                ..options
+            };
+            let mut report = build_report(&cli.directory, loaded_config.as_ref(), &options)?;
+            if let Some(path) = &baseline {
+                report.apply_baseline(&Baseline::load(path)?, path);
+            }
+            if let Some(changed) = &changed {
+                report.retain_files(changed);
+            }
+            let reporter: Box<dyn Reporter> = match format {
+                Format::Text => Box::new(TextReporter {
+                    verbose,
+                    color: use_color(),
+                }),
+                Format::Json => Box::new(JsonReporter),
+                Format::Sarif => Box::new(SarifReporter),
+                Format::Junit => Box::new(JunitReporter),
+                Format::Checkstyle => Box::new(CheckstyleReporter),
+                Format::Markdown => match &previous {
+                    Some(path) => {
+                        let previous = serde_json::from_str(&fs::read_to_string(path)?)?;
+                        Box::new(MarkdownReporter::with_previous_report(&previous))
                     }
+                    None => Box::new(MarkdownReporter::default()),
+                },
+            };
+            print!("{}", reporter.render(&report)?);
+
+            let threshold = fail_on.map(Severity::from).unwrap_or_else(|| {
+                loaded_config
+                    .as_ref()
+                    .and_then(|loaded| loaded.config.defaults.fail_on)
+                    .unwrap_or(Severity::Error)
+            });
+            if report.findings_at_or_above(threshold) > 0 {
+                return Ok(ExitCode::from(EXIT_FINDINGS));
+            }
+        }
+        Commands::Indexes { format } => {
+            let results = scan_project_with(&cli.directory, &options)?.queries;
+
+            if format == IndexFormat::Json {
+                let report = IndexReport::new(&cli.directory, &results);
+                println!("{}", serde_json::to_string_pretty(&report)?);
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            for collection_indexes in sorted_index_suggestions(&results) {
+                println!("Collection: {}", collection_indexes.collection);
+                for suggestion in &collection_indexes.suggestions {
+                    println!("   {}", suggestion);
                 }
                 println!();
             }
+        }
+        Commands::Writes => {
+            let results = scan_project_with(&cli.directory, &options)?.queries;
+            let writes = get_write_analysis(&results);
 
-            if let Some(loaded_config) = loaded_config {
-                let warnings = get_config_warnings(&results, &loaded_config.config);
-                if !warnings.is_empty() {
-                    println!("Config: {}", loaded_config.path.display());
-                    println!("Warnings:");
-                    for warning in warnings {
-                        match (&warning.file, warning.line) {
-                            (Some(file), Some(line)) => {
-                                println!(
-                                    "  [{}] {}:{} - {}",
-                                    warning.severity.as_str(),
-                                    file,
-                                    line,
-                                    warning.message
-                                );
-                            }
-                            _ => {
-                                println!("  [{}] {}", warning.severity.as_str(), warning.message);
-                            }
-                        }
+            for collection_data in &writes {
+                println!("Collection: {}", collection_data.collection);
+                for field_data in &collection_data.fields {
+                    println!("  {}", field_data.field);
+                    for writer in &field_data.writers {
+                        println!(
+                            "    {}:{} [{}] {}",
+                            writer.file, writer.line, writer.operation, writer.method
+                        );
                     }
-                    println!();
                 }
-            } else {
-                println!("Config: no redshift.config.json found (using defaults)");
                 println!();
             }
         }
-        Commands::Indexes => {
-            let results = analyze_project(&cli.directory)?;
-            let indexes = get_indexes(&results);
+        Commands::Baseline {
+            command: BaselineCommand::Write { output },
+        } => {
+            let report = build_report(&cli.directory, loaded_config.as_ref(), &options)?;
+            let path = output.unwrap_or_else(|| cli.directory.join(BASELINE_FILE_NAME));
+            Baseline::from_warnings(&report.warnings).write(&path)?;
+            println!(
+                "Wrote {} findings to {}",
+                report.warnings.len(),
+                path.display()
+            );
+        }
+        Commands::Inventory {
+            write,
+            check,
+            lockfile,
+        } => {
+            let results = scan_project_with(&cli.directory, &options)?.queries;
+            let inventory = Inventory::from_queries(&results);
+            let path = lockfile.unwrap_or_else(|| cli.directory.join(LOCK_FILE_NAME));
 
-            for (collection, suggestions) in indexes {
-                println!("Collection: {}", collection);
-                for suggestion in &suggestions {
-                    println!("   {}", suggestion);
+            if write {
+                inventory.write(&path)?;
+                println!(
+                    "Wrote {} query shapes to {}",
+                    inventory.shapes.len(),
+                    path.display()
+                );
+            } else if check {
+                let drift = Inventory::load(&path)?.drift(&inventory);
+                for entry in &drift.added {
+                    println!("+ {} {}", entry.id, entry.shape);
                 }
-                println!();
+                for entry in &drift.removed {
+                    println!("- {} {}", entry.id, entry.shape);
+                }
+                if !drift.added.is_empty() {
+                    eprintln!(
+                        "{} query shapes are not in {}; review them and run `redshift inventory --write`",
+                        drift.added.len(),
+                        path.display()
+                    );
+                    return Ok(ExitCode::from(EXIT_FINDINGS));
+                }
+            } else {
+                for entry in &inventory.shapes {
+                    println!("{} {}", entry.id, entry.shape);
+                }
+            }
+        }
+        Commands::Diff { old, new, format } => {
+            let diff = AnalysisDiff::between(
+                &load_analysis(&old, &options)?,
+                &load_analysis(&new, &options)?,
+            )?;
+            match format {
+                DiffFormat::Text => print!("{}", diff.to_text()),
+                DiffFormat::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
+            }
+        }
+        Commands::Lsp => {
+            let mut server = LanguageServer::new(&cli.directory, options);
+            lsp::run(&mut server, io::stdin().lock(), io::stdout().lock())?;
+        }
+        Commands::Graph { format, group_by } => {
+            let results = scan_project_with(&cli.directory, &options)?.queries;
+            let grouping = match group_by {
+                GraphGroupBy::Module => GraphGrouping::Module,
+                GraphGroupBy::Class => GraphGrouping::Class,
+            };
+
+            let graph = CollectionGraph::build(&cli.directory, &results, grouping);
+            match format {
+                GraphFormat::Dot => print!("{}", graph.to_dot()),
+                GraphFormat::Mermaid => print!("{}", graph.to_mermaid()),
             }
         }
+        Commands::Report { html } => {
+            let report = build_report(&cli.directory, loaded_config.as_ref(), &options)?;
+            fs::write(&html, HtmlReporter.render(&report)?)?;
+            println!("Wrote {}", html.display());
+        }
     }
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
+}
+
+/// How often `analyze --watch` checks for changed files.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Re-analyzes `directory` whenever a source file or the config changes, and
+/// prints the findings that appeared or were fixed since the last analysis.
+/// Only changed files are parsed again. Runs until interrupted.
+fn watch_project(
+    directory: &Path,
+    options: &ScanOptions,
+    verbose: bool,
+    baseline: Option<&Path>,
+) -> Result<ExitCode, Box<dyn Error>> {
+    let reporter = TextReporter {
+        verbose,
+        color: use_color(),
+    };
+    let analyze = |model: &ProjectModel| -> Result<AnalysisReport, Box<dyn Error>> {
+        let loaded_config = load_config(directory)?;
+        let project = model.project();
+        let mut report = AnalysisReport::new(directory, project.queries, loaded_config.as_ref());
+        report.add_parse_errors(&project.parse_errors);
+        report.apply_suppressions(&project.suppressions);
+        if let Some(path) = baseline {
+            report.apply_baseline(&Baseline::load(path)?, path);
+        }
+        Ok(report)
+    };
+    let config_stamp = || discover_config(directory).and_then(|path| stamp(&path));
+
+    let mut model = ProjectModel::load(directory, options);
+    let mut config = config_stamp();
+    let mut previous = analyze(&model)?;
+    print!("{}", reporter.render(&previous)?);
+    println!(
+        "Watching {} for changes (Ctrl-C to stop)",
+        directory.display()
+    );
+
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+        let current_config = config_stamp();
+        if current_config != config {
+            if let Ok(loaded_config) = load_config(directory) {
+                model.set_selection(scan_options(options, loaded_config.as_ref()).files);
+            }
+        }
+        let changed = model.refresh();
+        if changed.is_empty() && current_config == config {
+            continue;
+        }
+        config = current_config;
+
+        println!();
+        for path in &changed {
+            println!(
+                "{} {}",
+                reporter.paint("1", "changed:"),
+                relative_path(directory, &path.to_string_lossy())
+            );
+        }
+        let report = match analyze(&model) {
+            Ok(report) => report,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                continue;
+            }
+        };
+
+        let (appeared, fixed) = warning_changes(&previous.warnings, &report.warnings);
+        let appeared: Vec<&ConfigWarning> = appeared.iter().collect();
+        print!("{}", reporter.render_findings(&report, &appeared)?);
+        for warning in &fixed {
+            println!(
+                "{} [{}] {}",
+                reporter.paint("1;32", "fixed:"),
+                warning.rule.id(),
+                warning.message
+            );
+        }
+        print!("{}", reporter.render_summary(&report)?);
+        previous = report;
+    }
+}
+
+/// Colors only for terminals, and never when `NO_COLOR` is set.
+fn use_color() -> bool {
+    std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && io::stdout().is_terminal()
 }
@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use swc_core::common::{sync::Lrc, FileName, SourceMap};
+use swc_core::ecma::ast::*;
+
+use crate::ast_parser::parse_program;
+use crate::cache::content_hash;
+
+/// Export name under which a module's default export, or what it assigns to
+/// `module.exports`, is recorded.
+pub const DEFAULT_EXPORT: &str = "default";
+
+/// Extensions tried, in order, when resolving an import without one.
+const MODULE_EXTENSIONS: &[&str] = &["ts", "tsx", "js", "mjs", "cjs", "jsx"];
+
+/// Mongoose models exported by one project module.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleModels {
+    pub path: PathBuf,
+    /// Hash of the module's content, so results derived from it can be
+    /// invalidated when it changes.
+    pub hash: String,
+    /// Model name by export name.
+    pub exports: HashMap<String, String>,
+}
+
+/// The model name of `model('User', …)` or `mongoose.model('User', …)`.
+pub fn model_call_name(expr: &Expr) -> Option<String> {
+    let Expr::Call(call) = expr else {
+        return None;
+    };
+    let Callee::Expr(callee) = &call.callee else {
+        return None;
+    };
+    let is_model = match &**callee {
+        Expr::Ident(ident) => ident.sym == "model",
+        Expr::Member(member) => {
+            matches!(&*member.obj, Expr::Ident(obj) if obj.sym == "mongoose")
+                && matches!(&member.prop, MemberProp::Ident(prop) if prop.sym == "model")
+        }
+        _ => false,
+    };
+    if !is_model {
+        return None;
+    }
+    match call.args.first().map(|arg| &*arg.expr) {
+        Some(Expr::Lit(Lit::Str(name))) => name.value.as_str().map(str::to_string),
+        _ => None,
+    }
+}
+
+/// Hash recorded for a dependency on a project module that resolved to no
+/// file, so creating it later invalidates the results of its importer.
+pub const ABSENT_MODULE: &str = "";
+
+/// The path `specifier` names relative to the directory of `from`, before
+/// extensions are tried, or `None` if it is not a relative specifier.
+pub fn module_base(from: &Path, specifier: &str) -> Option<PathBuf> {
+    if !specifier.starts_with('.') {
+        return None;
+    }
+    Some(normalize(&from.parent()?.join(specifier)))
+}
+
+/// Resolves `specifier`, relative to the directory of `from`, to a file of
+/// the project: as written, with one of the source extensions, or as a
+/// directory's `index` file.
+pub fn resolve_module(from: &Path, specifier: &str) -> Option<PathBuf> {
+    resolve_base(&module_base(from, specifier)?)
+}
+
+/// The file a [`module_base`] resolves to.
+pub fn resolve_base(base: &Path) -> Option<PathBuf> {
+    if base.is_file() {
+        return Some(base.to_path_buf());
+    }
+    let with_extension = |path: &Path| {
+        MODULE_EXTENSIONS.iter().find_map(|extension| {
+            let mut candidate = path.as_os_str().to_owned();
+            candidate.push(format!(".{}", extension));
+            let candidate = PathBuf::from(candidate);
+            candidate.is_file().then_some(candidate)
+        })
+    };
+    with_extension(base).or_else(|| with_extension(&base.join("index")))
+}
+
+/// Drops `.` and folds `..` components, so a module imported through
+/// `../` has the same path as when it is found while walking the project.
+/// A leading `.` is kept for the same reason.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir if normalized.as_os_str().is_empty() => normalized.push(component),
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Reads and parses the module `specifier` imported from `from`, returning
+/// the models it exports. `None` if it cannot be found or read.
+pub fn load_module_models(from: &Path, specifier: &str) -> Option<ModuleModels> {
+    let path = resolve_module(from, specifier)?;
+    let content = fs::read_to_string(&path).ok()?;
+    let file_path = path.to_string_lossy().into_owned();
+
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(FileName::Custom(file_path.clone()).into(), content.clone());
+    let exports = match parse_program(&fm, &file_path, None).0 {
+        Ok(program) => exported_models(&program),
+        Err(_) => HashMap::new(),
+    };
+
+    Some(ModuleModels {
+        path,
+        hash: content_hash(&content),
+        exports,
+    })
+}
+
+/// Models exported by a module's top-level statements, by export name. Only
+/// values that are provably models count: `model(…)` calls, and names bound
+/// to one at the top level.
+pub fn exported_models(program: &Program) -> HashMap<String, String> {
+    let statements: Vec<&Stmt> = match program {
+        Program::Module(module) => module
+            .body
+            .iter()
+            .filter_map(|item| match item {
+                ModuleItem::Stmt(stmt) => Some(stmt),
+                ModuleItem::ModuleDecl(_) => None,
+            })
+            .collect(),
+        Program::Script(script) => script.body.iter().collect(),
+    };
+    let declarations = match program {
+        Program::Module(module) => module
+            .body
+            .iter()
+            .filter_map(|item| match item {
+                ModuleItem::ModuleDecl(decl) => Some(decl),
+                ModuleItem::Stmt(_) => None,
+            })
+            .collect(),
+        Program::Script(_) => Vec::new(),
+    };
+
+    let mut locals = HashMap::new();
+    for stmt in &statements {
+        if let Stmt::Decl(Decl::Var(var)) = stmt {
+            collect_model_vars(var, &mut locals);
+        }
+    }
+    for decl in &declarations {
+        if let ModuleDecl::ExportDecl(ExportDecl {
+            decl: Decl::Var(var),
+            ..
+        }) = decl
+        {
+            collect_model_vars(var, &mut locals);
+        }
+    }
+    let model_of = |expr: &Expr| match expr {
+        Expr::Ident(ident) => locals.get(ident.sym.as_str()).cloned(),
+        _ => model_call_name(expr),
+    };
+
+    let mut exports = HashMap::new();
+    for decl in declarations {
+        match decl {
+            ModuleDecl::ExportDecl(ExportDecl {
+                decl: Decl::Var(var),
+                ..
+            }) => {
+                for declarator in &var.decls {
+                    if let Pat::Ident(binding) = &declarator.name {
+                        let name = binding.id.sym.as_str();
+                        if let Some(model) = locals.get(name) {
+                            exports.insert(name.to_string(), model.clone());
+                        }
+                    }
+                }
+            }
+            ModuleDecl::ExportDefaultExpr(export) => {
+                if let Some(model) = model_of(&export.expr) {
+                    exports.insert(DEFAULT_EXPORT.to_string(), model);
+                }
+            }
+            ModuleDecl::ExportNamed(named) if named.src.is_none() => {
+                for specifier in &named.specifiers {
+                    let ExportSpecifier::Named(specifier) = specifier else {
+                        continue;
+                    };
+                    let ModuleExportName::Ident(orig) = &specifier.orig else {
+                        continue;
+                    };
+                    let exported = match &specifier.exported {
+                        Some(ModuleExportName::Ident(exported)) => exported.sym.to_string(),
+                        Some(_) => continue,
+                        None => orig.sym.to_string(),
+                    };
+                    if let Some(model) = locals.get(orig.sym.as_str()) {
+                        exports.insert(exported, model.clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // CommonJS: `module.exports = …`, `module.exports.X = …`, `exports.X = …`.
+    for stmt in statements {
+        let Stmt::Expr(ExprStmt { expr, .. }) = stmt else {
+            continue;
+        };
+        let Expr::Assign(assign) = &**expr else {
+            continue;
+        };
+        let Some(target) = assign
+            .left
+            .as_simple()
+            .and_then(|target| target.as_member())
+        else {
+            continue;
+        };
+        match commonjs_export_name(target) {
+            Some(None) => {
+                if let Some(model) = model_of(&assign.right) {
+                    exports.insert(DEFAULT_EXPORT.to_string(), model);
+                } else if let Expr::Object(object) = &*assign.right {
+                    for prop in &object.props {
+                        let PropOrSpread::Prop(prop) = prop else {
+                            continue;
+                        };
+                        let (name, model) = match &**prop {
+                            Prop::Shorthand(ident) => (
+                                ident.sym.to_string(),
+                                locals.get(ident.sym.as_str()).cloned(),
+                            ),
+                            Prop::KeyValue(kv) => match &kv.key {
+                                PropName::Ident(key) => (key.sym.to_string(), model_of(&kv.value)),
+                                PropName::Str(key) => (
+                                    key.value.as_str().unwrap_or_default().to_string(),
+                                    model_of(&kv.value),
+                                ),
+                                _ => continue,
+                            },
+                            _ => continue,
+                        };
+                        if let Some(model) = model {
+                            exports.insert(name, model);
+                        }
+                    }
+                }
+            }
+            Some(Some(name)) => {
+                if let Some(model) = model_of(&assign.right) {
+                    exports.insert(name, model);
+                }
+            }
+            None => {}
+        }
+    }
+    exports
+}
+
+/// Records `const User = model('User', …)` declarations in `locals`.
+fn collect_model_vars(var: &VarDecl, locals: &mut HashMap<String, String>) {
+    for declarator in &var.decls {
+        if let (Pat::Ident(binding), Some(init)) = (&declarator.name, &declarator.init) {
+            if let Some(model) = model_call_name(init) {
+                locals.insert(binding.id.sym.to_string(), model);
+            }
+        }
+    }
+}
+
+/// For an assignment target: `Some(None)` for `module.exports`,
+/// `Some(Some(name))` for `module.exports.name` or `exports.name`, and `None`
+/// for anything else.
+fn commonjs_export_name(target: &MemberExpr) -> Option<Option<String>> {
+    let MemberProp::Ident(prop) = &target.prop else {
+        return None;
+    };
+    match &*target.obj {
+        Expr::Ident(obj) if obj.sym == "module" && prop.sym == "exports" => Some(None),
+        Expr::Ident(obj) if obj.sym == "exports" => Some(Some(prop.sym.to_string())),
+        Expr::Member(inner) if commonjs_export_name(inner) == Some(None) => {
+            Some(Some(prop.sym.to_string()))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,546 @@
+pub mod checkstyle;
+pub mod html;
+pub mod junit;
+pub mod markdown;
+pub mod sarif;
+pub mod text;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast_parser::ParseError;
+use crate::baseline::{Baseline, BaselineSummary};
+use crate::config::{ConfigWarning, LoadedConfig, Rule, Severity};
+use crate::suppression::{self, Suppression};
+use crate::{
+    get_collection_analysis, get_config_warnings, get_index_coverage, get_indexes, relative_path,
+    sort_warnings, CollectionAnalysis, FileCategory, IndexCoverage, IndexSuggestion, MongoQuery,
+    ResolutionStatus,
+};
+
+pub use checkstyle::CheckstyleReporter;
+pub use html::HtmlReporter;
+pub use junit::JunitReporter;
+pub use markdown::MarkdownReporter;
+pub use sarif::SarifReporter;
+pub use text::TextReporter;
+
+/// Renders an [`AnalysisReport`] in one output format.
+pub trait Reporter {
+    fn render(&self, report: &AnalysisReport) -> Result<String, Box<dyn Error>>;
+}
+
+/// The `--format json` document described in `docs/JSON_OUTPUT.md`.
+pub struct JsonReporter;
+
+impl Reporter for JsonReporter {
+    fn render(&self, report: &AnalysisReport) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(report)?)
+    }
+}
+
+/// Version of the machine-readable report schema documented in
+/// `docs/JSON_OUTPUT.md`. Bumped on any breaking change to field names or types.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionIndexes {
+    pub collection: String,
+    pub suggestions: Vec<IndexSuggestion>,
+}
+
+/// Everything `analyze` knows about a project, in the shape emitted by
+/// `--format json`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisReport {
+    pub schema_version: u32,
+    pub tool_version: String,
+    pub root: String,
+    pub config_path: Option<String>,
+    pub queries: Vec<MongoQuery>,
+    pub collections: Vec<CollectionAnalysis>,
+    pub index_suggestions: Vec<CollectionIndexes>,
+    /// Configured-index coverage per configured collection.
+    pub index_coverage: Vec<IndexCoverage>,
+    pub warnings: Vec<ConfigWarning>,
+    /// Set when findings were filtered through `analyze --baseline`.
+    pub baseline: Option<BaselineSummary>,
+    /// How many query sites were fully understood.
+    pub resolution: ResolutionSummary,
+}
+
+/// Counts of query sites by [`ResolutionStatus`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolutionSummary {
+    pub queries: usize,
+    pub resolved: usize,
+    pub partial: usize,
+    pub unresolved: usize,
+    /// Files skipped because they could not be parsed.
+    pub unparsed_files: usize,
+}
+
+impl ResolutionSummary {
+    pub fn of(queries: &[MongoQuery]) -> Self {
+        let count = |status| {
+            queries
+                .iter()
+                .filter(|query| query.resolution.status == status)
+                .count()
+        };
+        Self {
+            queries: queries.len(),
+            resolved: count(ResolutionStatus::Resolved),
+            partial: count(ResolutionStatus::Partial),
+            unresolved: count(ResolutionStatus::Unresolved),
+            unparsed_files: 0,
+        }
+    }
+
+    /// Percentage of query sites fully resolved; 100 when there are none.
+    pub fn resolved_percent(&self) -> f64 {
+        if self.queries == 0 {
+            100.0
+        } else {
+            self.resolved as f64 * 100.0 / self.queries as f64
+        }
+    }
+}
+
+/// Output of `indexes --format json`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexReport {
+    pub schema_version: u32,
+    pub tool_version: String,
+    pub root: String,
+    pub index_suggestions: Vec<CollectionIndexes>,
+}
+
+impl AnalysisReport {
+    pub fn new(root: &Path, queries: Vec<MongoQuery>, config: Option<&LoadedConfig>) -> Self {
+        let collections = get_collection_analysis(&queries);
+        let resolution = ResolutionSummary::of(&queries);
+        // Test files are listed, but what they query is not what production
+        // runs, so they get no findings and drive no index suggestions.
+        let source: Vec<MongoQuery> = queries
+            .iter()
+            .filter(|query| query.category == FileCategory::Source)
+            .cloned()
+            .collect();
+        let index_suggestions = sorted_index_suggestions(&source);
+        let warnings = config
+            .map(|loaded| get_config_warnings(&source, &loaded.config))
+            .unwrap_or_default();
+        let index_coverage = config
+            .map(|loaded| get_index_coverage(&source, &loaded.config))
+            .unwrap_or_default();
+
+        Self {
+            schema_version: SCHEMA_VERSION,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            root: root.display().to_string(),
+            config_path: config.map(|loaded| loaded.path.display().to_string()),
+            queries,
+            collections,
+            index_suggestions,
+            index_coverage,
+            warnings,
+            baseline: None,
+            resolution,
+        }
+    }
+
+    /// Reports syntax errors as `parse-error` findings, and counts files the
+    /// parser gave up on. Call before [`Self::apply_suppressions`] so they can
+    /// be suppressed too.
+    pub fn add_parse_errors(&mut self, errors: &[ParseError]) {
+        let root = Path::new(&self.root);
+        for error in errors {
+            let message = if error.fatal {
+                format!("Failed to parse, file skipped: {}", error.message)
+            } else {
+                format!("Syntax error: {}", error.message)
+            };
+            let relative = relative_path(root, &error.file);
+            let key = format!("{}:{}", relative, error.message);
+            self.warnings.push(ConfigWarning::at_location(
+                Rule::ParseError,
+                Severity::Warning,
+                message,
+                &error.file,
+                error.line,
+                error.column,
+                &key,
+            ));
+        }
+        self.resolution.unparsed_files += errors.iter().filter(|error| error.fatal).count();
+        sort_warnings(&mut self.warnings);
+    }
+
+    /// Drops findings hidden by inline suppression comments, and reports the
+    /// comments that are unused or give no reason. Without a config the
+    /// config rules never ran, so their suppressions are not reported unused.
+    pub fn apply_suppressions(&mut self, suppressions: &[Suppression]) {
+        let warnings = std::mem::take(&mut self.warnings);
+        self.warnings = suppression::apply(
+            warnings,
+            suppressions,
+            Path::new(&self.root),
+            self.config_path.is_some(),
+        );
+        sort_warnings(&mut self.warnings);
+    }
+
+    /// Drops the findings `baseline` accepts and records what it suppressed
+    /// and which of its entries are fixed.
+    pub fn apply_baseline(&mut self, baseline: &Baseline, path: &Path) {
+        let warnings = std::mem::take(&mut self.warnings);
+        let (warnings, summary) = baseline.apply(path, warnings);
+        self.warnings = warnings;
+        self.baseline = Some(summary);
+    }
+
+    /// Narrows the report to `files`, paths relative to the root: findings in
+    /// other files are dropped, as are per-file query listings. Project-level
+    /// findings stay when a listed file queries their collection or the config
+    /// file is listed. Queries, index suggestions and coverage still describe
+    /// the whole tree.
+    pub fn retain_files(&mut self, files: &BTreeSet<String>) {
+        let root = Path::new(&self.root);
+        let config_changed = self
+            .config_path
+            .as_deref()
+            .is_some_and(|config| files.contains(&relative_path(root, config)));
+        let touched: BTreeSet<&str> = self
+            .queries
+            .iter()
+            .filter(|query| files.contains(&relative_path(root, &query.file)))
+            .map(|query| query.collection.as_str())
+            .collect();
+
+        self.warnings
+            .retain(|warning| match (&warning.file, &warning.collection) {
+                (Some(file), _) => files.contains(&relative_path(root, file)),
+                (None, Some(collection)) => config_changed || touched.contains(collection.as_str()),
+                (None, None) => config_changed,
+            });
+        for collection in &mut self.collections {
+            collection
+                .files
+                .retain(|file| files.contains(&relative_path(root, &file.file_path)));
+        }
+        self.collections
+            .retain(|collection| !collection.files.is_empty());
+    }
+
+    /// Drops project-level findings, which are only meaningful when every
+    /// file was analyzed.
+    pub fn retain_file_findings(&mut self) {
+        self.warnings.retain(|warning| warning.file.is_some());
+    }
+
+    /// Warnings grouped by the file they point at, relative to the analyzed
+    /// root and ordered by path. Project-level warnings are grouped under the
+    /// config file, since that is where they are fixed.
+    pub fn warnings_by_file(&self) -> BTreeMap<String, Vec<&ConfigWarning>> {
+        let root = Path::new(&self.root);
+        let project_file = self
+            .config_path
+            .as_deref()
+            .map(|config| relative_path(root, config))
+            .unwrap_or_else(|| "redshift.config.json".to_string());
+
+        let mut grouped: BTreeMap<String, Vec<&ConfigWarning>> = BTreeMap::new();
+        for warning in &self.warnings {
+            let file = match &warning.file {
+                Some(file) => relative_path(root, file),
+                None => project_file.clone(),
+            };
+            grouped.entry(file).or_default().push(warning);
+        }
+        grouped
+    }
+
+    /// Number of findings, across every rule, at or above `severity`.
+    pub fn findings_at_or_above(&self, severity: Severity) -> usize {
+        self.warnings
+            .iter()
+            .filter(|warning| warning.severity >= severity)
+            .count()
+    }
+
+    /// Files that contain at least one query, relative to the analyzed root.
+    pub fn analyzed_files(&self) -> Vec<String> {
+        let root = Path::new(&self.root);
+        let mut files: Vec<String> = self
+            .queries
+            .iter()
+            .map(|query| relative_path(root, &query.file))
+            .collect();
+        files.sort();
+        files.dedup();
+        files
+    }
+}
+
+impl IndexReport {
+    pub fn new(root: &Path, queries: &[MongoQuery]) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            root: root.display().to_string(),
+            index_suggestions: sorted_index_suggestions(queries),
+        }
+    }
+}
+
+/// Escapes text for use in XML (and HTML) content and attribute values.
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Source files read on demand for snippets, each at most once.
+#[derive(Default)]
+pub(crate) struct SourceCache {
+    files: HashMap<String, Option<Vec<String>>>,
+}
+
+impl SourceCache {
+    /// The file's lines, or `None` if it cannot be read.
+    pub(crate) fn lines(&mut self, file: &str) -> Option<&[String]> {
+        self.files
+            .entry(file.to_string())
+            .or_insert_with(|| {
+                fs::read_to_string(file)
+                    .ok()
+                    .map(|content| content.lines().map(str::to_string).collect())
+            })
+            .as_deref()
+    }
+}
+
+/// Index suggestions ordered by collection name, so output is stable.
+pub fn sorted_index_suggestions(queries: &[MongoQuery]) -> Vec<CollectionIndexes> {
+    let mut suggestions: Vec<CollectionIndexes> = get_indexes(queries)
+        .into_iter()
+        .map(|(collection, suggestions)| CollectionIndexes {
+            collection,
+            suggestions,
+        })
+        .collect();
+    suggestions.sort_by(|a, b| a.collection.cmp(&b.collection));
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::path::Path;
+
+    use super::{AnalysisReport, SCHEMA_VERSION};
+    use crate::ast_parser::{parse_source, ParseError};
+    use crate::config::{ConfigWarning, Rule, Severity};
+    use crate::{MongoQuery, OperationClass};
+
+    #[test]
+    fn serializes_documented_top_level_fields() {
+        let queries = vec![MongoQuery {
+            file: "src/user.service.ts".to_string(),
+            line: 10,
+            collection: "users".to_string(),
+            method: "find".to_string(),
+            operation: OperationClass::Read,
+            query_fields: vec!["email".to_string()],
+            raw_match: "users.find({ email })".to_string(),
+            ..Default::default()
+        }];
+
+        let report = AnalysisReport::new(Path::new("."), queries, None);
+        let value = serde_json::to_value(&report).unwrap();
+
+        assert_eq!(value["schemaVersion"], SCHEMA_VERSION);
+        assert_eq!(value["queries"][0]["queryFields"][0], "email");
+        assert_eq!(value["queries"][0]["operation"], "read");
+        assert_eq!(
+            value["collections"][0]["files"][0]["patterns"][0]["count"],
+            1
+        );
+        assert!(value["configPath"].is_null());
+        assert!(value["warnings"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn counts_findings_of_every_rule_at_or_above_severity() {
+        let query = MongoQuery {
+            collection: "users".to_string(),
+            ..Default::default()
+        };
+        let mut report = AnalysisReport::new(Path::new("."), vec![query.clone()], None);
+        report.warnings = vec![
+            ConfigWarning::project(
+                Rule::UnknownCollection,
+                Severity::Warning,
+                "orders",
+                String::new(),
+            ),
+            ConfigWarning::at_query(
+                Rule::FullDocumentRead,
+                Severity::Info,
+                String::new(),
+                &query,
+            ),
+            ConfigWarning::at_query(
+                Rule::RecommendedPredicateMiss,
+                Severity::Error,
+                String::new(),
+                &query,
+            ),
+        ];
+
+        assert_eq!(report.findings_at_or_above(Severity::Info), 3);
+        assert_eq!(report.findings_at_or_above(Severity::Warning), 2);
+        assert_eq!(report.findings_at_or_above(Severity::Error), 1);
+    }
+
+    #[test]
+    fn keeps_findings_in_order_after_suppressions() {
+        let source = "// redshift-ignore-next-line full-document-read\n\
+                      db.collection('users').find({ email });\n\
+                      db.collection('users').find({ role });";
+        let parsed = parse_source(source, "users.ts");
+        let mut report = AnalysisReport::new(Path::new("."), parsed.queries.clone(), None);
+        report.warnings = parsed
+            .queries
+            .iter()
+            .map(|query| {
+                ConfigWarning::at_query(
+                    Rule::FullDocumentRead,
+                    Severity::Warning,
+                    String::new(),
+                    query,
+                )
+            })
+            .collect();
+
+        report.apply_suppressions(&parsed.suppressions);
+        let found: Vec<(Rule, Option<usize>)> = report
+            .warnings
+            .iter()
+            .map(|warning| (warning.rule, warning.line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Rule::SuppressionWithoutReason, Some(1)),
+                (Rule::FullDocumentRead, Some(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_findings_in_order_after_parse_errors() {
+        let query = MongoQuery {
+            file: "users.ts".to_string(),
+            line: 5,
+            collection: "users".to_string(),
+            ..Default::default()
+        };
+        let mut report = AnalysisReport::new(Path::new("."), vec![query.clone()], None);
+        report.warnings = vec![ConfigWarning::at_query(
+            Rule::FullDocumentRead,
+            Severity::Warning,
+            String::new(),
+            &query,
+        )];
+
+        report.add_parse_errors(&[ParseError {
+            file: "users.ts".to_string(),
+            line: 1,
+            column: 1,
+            message: "Unexpected token".to_string(),
+            fatal: false,
+        }]);
+        let rules: Vec<Rule> = report.warnings.iter().map(|warning| warning.rule).collect();
+        assert_eq!(rules, vec![Rule::ParseError, Rule::FullDocumentRead]);
+    }
+
+    #[test]
+    fn retains_findings_of_changed_files_and_their_collections() {
+        let query = |file: &str, collection: &str| MongoQuery {
+            file: format!("./src/{}", file),
+            line: 1,
+            collection: collection.to_string(),
+            query_fields: vec!["status".to_string()],
+            ..Default::default()
+        };
+        let changed = query("orders.ts", "orders");
+        let unchanged = query("users.ts", "users");
+        let mut report = AnalysisReport::new(
+            Path::new("."),
+            vec![changed.clone(), unchanged.clone()],
+            None,
+        );
+        report.warnings = vec![
+            ConfigWarning::at_query(
+                Rule::FullDocumentRead,
+                Severity::Info,
+                String::new(),
+                &changed,
+            ),
+            ConfigWarning::at_query(
+                Rule::FullDocumentRead,
+                Severity::Info,
+                String::new(),
+                &unchanged,
+            ),
+            ConfigWarning::project(
+                Rule::UnknownCollection,
+                Severity::Warning,
+                "orders",
+                String::new(),
+            ),
+            ConfigWarning::project(
+                Rule::UnknownCollection,
+                Severity::Warning,
+                "users",
+                String::new(),
+            ),
+        ];
+
+        report.retain_files(&BTreeSet::from(["src/orders.ts".to_string()]));
+
+        let kept: Vec<(Rule, Option<&str>)> = report
+            .warnings
+            .iter()
+            .map(|warning| (warning.rule, warning.collection.as_deref()))
+            .collect();
+        assert_eq!(
+            kept,
+            vec![
+                (Rule::FullDocumentRead, Some("orders")),
+                (Rule::UnknownCollection, Some("orders")),
+            ]
+        );
+        assert_eq!(report.collections.len(), 1);
+        assert_eq!(report.queries.len(), 2);
+    }
+}
@@ -0,0 +1,73 @@
+use std::error::Error;
+use std::fmt::Write;
+
+use super::{escape_xml, AnalysisReport, Reporter};
+use crate::config::Severity;
+
+/// Checkstyle XML, one `<file>` element per file with warnings.
+pub struct CheckstyleReporter;
+
+fn severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info => "info",
+    }
+}
+
+impl Reporter for CheckstyleReporter {
+    fn render(&self, report: &AnalysisReport) -> Result<String, Box<dyn Error>> {
+        let mut out = String::new();
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(out, r#"<checkstyle version="4.3">"#)?;
+
+        for (file, warnings) in report.warnings_by_file() {
+            writeln!(out, r#"  <file name="{}">"#, escape_xml(&file))?;
+            for warning in warnings {
+                write!(out, r#"    <error line="{}""#, warning.line.unwrap_or(1))?;
+                if let Some(column) = warning.column {
+                    write!(out, r#" column="{}""#, column)?;
+                }
+                writeln!(
+                    out,
+                    r#" severity="{}" message="{}" source="redshift.{}" />"#,
+                    severity(warning.severity),
+                    escape_xml(&warning.message),
+                    warning.rule.id()
+                )?;
+            }
+            writeln!(out, "  </file>")?;
+        }
+
+        writeln!(out, "</checkstyle>")?;
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::CheckstyleReporter;
+    use crate::config::{ConfigWarning, Rule, Severity};
+    use crate::report::{AnalysisReport, Reporter};
+
+    #[test]
+    fn project_warnings_are_reported_on_the_config_file() {
+        let mut report = AnalysisReport::new(Path::new("app"), Vec::new(), None);
+        report.config_path = Some("app/redshift.config.json".to_string());
+        report.warnings = vec![ConfigWarning::project(
+            Rule::UnknownCollection,
+            Severity::Warning,
+            "orders",
+            "Collection 'orders' is queried 2 times but is not configured".to_string(),
+        )];
+
+        let xml = CheckstyleReporter.render(&report).unwrap();
+
+        assert!(xml.contains(r#"<file name="redshift.config.json">"#));
+        assert!(xml.contains(r#"severity="warning""#));
+        assert!(xml.contains(r#"source="redshift.unknown-collection""#));
+        assert!(xml.contains("&apos;orders&apos;"));
+    }
+}
@@ -0,0 +1,315 @@
+use std::error::Error;
+use std::fmt::Write;
+use std::path::Path;
+
+use super::{escape_xml, AnalysisReport, Reporter, SourceCache};
+use crate::config::ConfigWarning;
+use crate::relative_path;
+
+/// Longest source excerpt shown under a warning.
+const MAX_SNIPPET_LINES: usize = 8;
+
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 2rem; color: #1f2328; }
+h1 { margin-bottom: 0.25rem; }
+.meta { color: #59636e; margin-top: 0; }
+.toolbar { position: sticky; top: 0; background: #fff; padding: 0.5rem 0; border-bottom: 1px solid #d1d9e0; }
+.toolbar input[type=search] { width: 24rem; padding: 0.25rem; }
+.summary { display: flex; gap: 1rem; margin: 1rem 0; }
+.card { border: 1px solid #d1d9e0; border-radius: 6px; padding: 0.5rem 1rem; }
+.card b { display: block; font-size: 1.5rem; }
+details { margin: 0.25rem 0 0.25rem 1rem; }
+summary { cursor: pointer; }
+table { border-collapse: collapse; margin: 0.5rem 0; }
+th, td { border: 1px solid #d1d9e0; padding: 0.25rem 0.5rem; text-align: left; vertical-align: top; }
+pre { background: #f6f8fa; padding: 0.5rem; overflow-x: auto; margin: 0.25rem 0; }
+.sev { font-weight: bold; text-transform: uppercase; font-size: 0.8rem; }
+.sev-error { color: #cf222e; }
+.sev-warning { color: #9a6700; }
+.sev-info { color: #0969da; }
+.hidden { display: none; }
+"#;
+
+const SCRIPT: &str = r#"
+function applyFilters() {
+  var text = document.getElementById('filter').value.toLowerCase();
+  var severities = {};
+  document.querySelectorAll('.sev-toggle').forEach(function (box) {
+    severities[box.value] = box.checked;
+  });
+  document.querySelectorAll('[data-filter]').forEach(function (el) {
+    var matchesText = !text || el.getAttribute('data-filter').indexOf(text) !== -1;
+    var severity = el.getAttribute('data-severity');
+    var matchesSeverity = !severity || severities[severity];
+    el.classList.toggle('hidden', !(matchesText && matchesSeverity));
+  });
+}
+document.getElementById('filter').addEventListener('input', applyFilters);
+document.querySelectorAll('.sev-toggle').forEach(function (box) {
+  box.addEventListener('change', applyFilters);
+});
+"#;
+
+/// A single offline HTML page with collections drilled down to files,
+/// patterns and query sites, plus warnings with source snippets, index
+/// suggestions and coverage. Filtering runs client-side; there are no
+/// external assets.
+pub struct HtmlReporter;
+
+impl Reporter for HtmlReporter {
+    fn render(&self, report: &AnalysisReport) -> Result<String, Box<dyn Error>> {
+        let root = Path::new(&report.root);
+        let mut sources = SourceCache::default();
+        let mut out = String::new();
+
+        writeln!(out, "<!DOCTYPE html>")?;
+        writeln!(out, r#"<html lang="en"><head><meta charset="utf-8">"#)?;
+        writeln!(out, "<title>redshift report</title>")?;
+        writeln!(out, "<style>{}</style></head><body>", STYLE)?;
+        writeln!(out, "<h1>redshift report</h1>")?;
+        writeln!(
+            out,
+            r#"<p class="meta">Root <code>{}</code> &middot; config <code>{}</code> &middot; redshift {}</p>"#,
+            escape_xml(&report.root),
+            escape_xml(report.config_path.as_deref().unwrap_or("none")),
+            escape_xml(&report.tool_version)
+        )?;
+
+        writeln!(out, r#"<div class="toolbar">"#)?;
+        writeln!(
+            out,
+            r#"<input id="filter" type="search" placeholder="Filter by collection, file, field or message">"#
+        )?;
+        for severity in ["error", "warning", "info"] {
+            writeln!(
+                out,
+                r#"<label><input class="sev-toggle" type="checkbox" value="{0}" checked> {0}</label>"#,
+                severity
+            )?;
+        }
+        writeln!(out, "</div>")?;
+
+        let file_count = report.analyzed_files().len();
+        writeln!(out, r#"<div class="summary">"#)?;
+        for (label, value) in [
+            ("Collections", report.collections.len()),
+            ("Files", file_count),
+            ("Query sites", report.queries.len()),
+            ("Warnings", report.warnings.len()),
+        ] {
+            writeln!(out, r#"<div class="card"><b>{}</b>{}</div>"#, value, label)?;
+        }
+        writeln!(
+            out,
+            r#"<div class="card"><b>{:.0}%</b>Fully resolved</div>"#,
+            report.resolution.resolved_percent()
+        )?;
+        writeln!(out, "</div>")?;
+
+        writeln!(out, "<h2>Warnings</h2>")?;
+        if report.warnings.is_empty() {
+            writeln!(out, "<p>No warnings.</p>")?;
+        }
+        for warning in &report.warnings {
+            render_warning(&mut out, warning, root, &mut sources)?;
+        }
+
+        writeln!(out, "<h2>Collections</h2>")?;
+        for collection in &report.collections {
+            let sites: usize = collection
+                .files
+                .iter()
+                .flat_map(|file| &file.patterns)
+                .map(|pattern| pattern.count)
+                .sum();
+            writeln!(
+                out,
+                r#"<details data-filter="{}"><summary><b>{}</b> &mdash; {} query sites in {} files</summary>"#,
+                escape_xml(&collection_filter_text(collection)),
+                escape_xml(&collection.collection),
+                sites,
+                collection.files.len()
+            )?;
+            for file in &collection.files {
+                writeln!(
+                    out,
+                    "<details><summary><code>{}</code></summary>",
+                    escape_xml(&relative_path(root, &file.file_path))
+                )?;
+                writeln!(
+                    out,
+                    "<table><tr><th>Shape</th><th>Usages</th><th>Sites</th></tr>"
+                )?;
+                for pattern in &file.patterns {
+                    write!(
+                        out,
+                        "<tr><td><code>{}</code></td><td>{}</td><td>",
+                        escape_xml(&pattern.shape),
+                        pattern.count
+                    )?;
+                    for query in &pattern.queries {
+                        write!(
+                            out,
+                            "<div>line {} <span class=\"meta\">[{}]</span><pre>{}</pre></div>",
+                            query.line,
+                            query.operation,
+                            escape_xml(&query.raw_match)
+                        )?;
+                    }
+                    writeln!(out, "</td></tr>")?;
+                }
+                writeln!(out, "</table></details>")?;
+            }
+            writeln!(out, "</details>")?;
+        }
+
+        writeln!(out, "<h2>Index suggestions</h2>")?;
+        if report.index_suggestions.is_empty() {
+            writeln!(out, "<p>No suggestions.</p>")?;
+        }
+        for collection in &report.index_suggestions {
+            writeln!(
+                out,
+                r#"<div data-filter="{}"><b>{}</b><ul>"#,
+                escape_xml(&collection.collection.to_lowercase()),
+                escape_xml(&collection.collection)
+            )?;
+            for suggestion in &collection.suggestions {
+                writeln!(out, "<li>{}</li>", escape_xml(&suggestion.to_string()))?;
+            }
+            writeln!(out, "</ul></div>")?;
+        }
+
+        writeln!(out, "<h2>Index coverage</h2>")?;
+        if report.index_coverage.is_empty() {
+            writeln!(out, "<p>No configured collections.</p>")?;
+        } else {
+            writeln!(
+                out,
+                "<table><tr><th>Collection</th><th>Filtered sites</th><th>Using a configured index</th><th>Unsupported filters</th></tr>"
+            )?;
+            for coverage in &report.index_coverage {
+                let unsupported: Vec<String> = coverage
+                    .unsupported_patterns
+                    .iter()
+                    .map(|fields| format!("[{}]", fields.join(", ")))
+                    .collect();
+                writeln!(
+                    out,
+                    r#"<tr data-filter="{}"><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>"#,
+                    escape_xml(&coverage.collection.to_lowercase()),
+                    escape_xml(&coverage.collection),
+                    coverage.queries,
+                    coverage.supported,
+                    escape_xml(&unsupported.join(" "))
+                )?;
+            }
+            writeln!(out, "</table>")?;
+        }
+
+        writeln!(out, "<script>{}</script>", SCRIPT)?;
+        writeln!(out, "</body></html>")?;
+        Ok(out)
+    }
+}
+
+fn collection_filter_text(collection: &crate::CollectionAnalysis) -> String {
+    let mut text = collection.collection.clone();
+    for file in &collection.files {
+        text.push(' ');
+        text.push_str(&file.file_path);
+        for pattern in &file.patterns {
+            text.push(' ');
+            text.push_str(&pattern.fields.join(" "));
+        }
+    }
+    text.to_lowercase()
+}
+
+fn render_warning(
+    out: &mut String,
+    warning: &ConfigWarning,
+    root: &Path,
+    sources: &mut SourceCache,
+) -> Result<(), Box<dyn Error>> {
+    let severity = warning.severity.as_str();
+    let location = match (&warning.file, warning.line) {
+        (Some(file), Some(line)) => format!("{}:{}", relative_path(root, file), line),
+        _ => "project".to_string(),
+    };
+    let filter_text = format!("{} {} {}", location, warning.rule.id(), warning.message);
+
+    writeln!(
+        out,
+        r#"<div data-filter="{}" data-severity="{}">"#,
+        escape_xml(&filter_text.to_lowercase()),
+        severity
+    )?;
+    writeln!(
+        out,
+        r#"<p><span class="sev sev-{0}">{0}</span> <code>{1}</code> {2} <span class="meta">[{3}]</span></p>"#,
+        severity,
+        escape_xml(&location),
+        escape_xml(&warning.message),
+        warning.rule.id()
+    )?;
+    if let (Some(file), Some(line)) = (&warning.file, warning.line) {
+        let end_line = warning.end_line.unwrap_or(line);
+        if let Some(snippet) = snippet(sources, file, line, end_line) {
+            writeln!(out, "<pre>{}</pre>", escape_xml(&snippet))?;
+        }
+    }
+    writeln!(out, "</div>")?;
+    Ok(())
+}
+
+fn snippet(sources: &mut SourceCache, file: &str, line: usize, end_line: usize) -> Option<String> {
+    let lines = sources.lines(file)?;
+    let first = line.checked_sub(1)?;
+    let last = end_line.max(line).min(first + MAX_SNIPPET_LINES);
+    let excerpt: Vec<String> = lines
+        .get(first..last.min(lines.len()))?
+        .iter()
+        .zip(line..)
+        .map(|(text, number)| format!("{:>5} | {}", number, text))
+        .collect();
+    Some(excerpt.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::HtmlReporter;
+    use crate::config::{ConfigWarning, Rule, Severity};
+    use crate::report::{AnalysisReport, Reporter};
+    use crate::MongoQuery;
+
+    #[test]
+    fn renders_escaped_self_contained_page() {
+        let query = MongoQuery {
+            file: "app/src/user.service.ts".to_string(),
+            line: 4,
+            collection: "users".to_string(),
+            method: "find".to_string(),
+            query_fields: vec!["email".to_string()],
+            raw_match: "users.find({ email: '<script>' })".to_string(),
+            ..Default::default()
+        };
+        let mut report = AnalysisReport::new(Path::new("app"), vec![query.clone()], None);
+        report.warnings = vec![ConfigWarning::at_query(
+            Rule::RecommendedPredicateMiss,
+            Severity::Warning,
+            "missing tenant".to_string(),
+            &query,
+        )];
+
+        let html = HtmlReporter.render(&report).unwrap();
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(!html.contains("<link") && !html.contains("src=\"http"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(html.contains("<code>src/user.service.ts:4</code>"));
+        assert!(html.contains(r#"data-severity="warning""#));
+    }
+}
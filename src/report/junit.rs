@@ -0,0 +1,128 @@
+use std::error::Error;
+use std::fmt::Write;
+
+use super::{escape_xml, AnalysisReport, Reporter};
+
+/// JUnit XML with one test suite per file and one failing test case per
+/// warning. Files with queries but no warnings get a single passing case, so
+/// CI test tabs show what was checked.
+pub struct JunitReporter;
+
+impl Reporter for JunitReporter {
+    fn render(&self, report: &AnalysisReport) -> Result<String, Box<dyn Error>> {
+        let warnings_by_file = report.warnings_by_file();
+        let mut files = report.analyzed_files();
+        files.extend(warnings_by_file.keys().cloned());
+        files.sort();
+        files.dedup();
+
+        let total_tests: usize = files
+            .iter()
+            .map(|file| warnings_by_file.get(file).map_or(1, Vec::len))
+            .sum();
+
+        let mut out = String::new();
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            out,
+            r#"<testsuites name="redshift" tests="{}" failures="{}">"#,
+            total_tests,
+            report.warnings.len()
+        )?;
+
+        for file in &files {
+            let file_attr = escape_xml(file);
+            match warnings_by_file.get(file) {
+                Some(warnings) => {
+                    writeln!(
+                        out,
+                        r#"  <testsuite name="{}" tests="{}" failures="{}">"#,
+                        file_attr,
+                        warnings.len(),
+                        warnings.len()
+                    )?;
+                    for warning in warnings {
+                        let location = match (warning.line, warning.column) {
+                            (Some(line), Some(column)) => format!("{}:{}:{}", file, line, column),
+                            (Some(line), None) => format!("{}:{}", file, line),
+                            _ => file.clone(),
+                        };
+                        writeln!(
+                            out,
+                            r#"    <testcase classname="{}" name="{}">"#,
+                            file_attr,
+                            escape_xml(&format!("redshift.{}", warning.rule.id()))
+                        )?;
+                        writeln!(
+                            out,
+                            r#"      <failure type="{}" message="{}">{} [{}] {}</failure>"#,
+                            warning.severity.as_str(),
+                            escape_xml(&warning.message),
+                            escape_xml(&location),
+                            warning.severity.as_str(),
+                            escape_xml(&warning.message)
+                        )?;
+                        writeln!(out, "    </testcase>")?;
+                    }
+                    writeln!(out, "  </testsuite>")?;
+                }
+                None => {
+                    writeln!(
+                        out,
+                        r#"  <testsuite name="{}" tests="1" failures="0">"#,
+                        file_attr
+                    )?;
+                    writeln!(
+                        out,
+                        r#"    <testcase classname="{}" name="{}" />"#,
+                        file_attr, file_attr
+                    )?;
+                    writeln!(out, "  </testsuite>")?;
+                }
+            }
+        }
+
+        writeln!(out, "</testsuites>")?;
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::JunitReporter;
+    use crate::config::{ConfigWarning, Rule, Severity};
+    use crate::report::{AnalysisReport, Reporter};
+    use crate::MongoQuery;
+
+    #[test]
+    fn groups_failures_by_file_and_keeps_clean_files() {
+        let flagged = MongoQuery {
+            file: "app/src/user.service.ts".to_string(),
+            line: 3,
+            column: 9,
+            collection: "users".to_string(),
+            method: "find".to_string(),
+            ..Default::default()
+        };
+        let clean = MongoQuery {
+            file: "app/src/order.service.ts".to_string(),
+            ..flagged.clone()
+        };
+        let mut report = AnalysisReport::new(Path::new("app"), vec![flagged.clone(), clean], None);
+        report.warnings = vec![ConfigWarning::at_query(
+            Rule::RecommendedPredicateMiss,
+            Severity::Error,
+            "needs <tenantId>".to_string(),
+            &flagged,
+        )];
+
+        let xml = JunitReporter.render(&report).unwrap();
+
+        assert!(xml.contains(r#"<testsuites name="redshift" tests="2" failures="1">"#));
+        assert!(xml.contains(r#"<testsuite name="src/order.service.ts" tests="1" failures="0">"#));
+        assert!(xml.contains(r#"<testsuite name="src/user.service.ts" tests="1" failures="1">"#));
+        assert!(xml.contains("src/user.service.ts:3:9 [error] needs &lt;tenantId&gt;"));
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::Write;
+use std::path::Path;
+
+use serde_json::Value;
+
+use super::{AnalysisReport, Reporter};
+use crate::config::Severity;
+use crate::shape::QueryShape;
+use crate::{relative_path, MongoQuery, OperationClass};
+
+/// A compact summary meant to be pasted into pull request comments.
+///
+/// When `previous_shapes` holds the shape IDs of an earlier run, only shapes
+/// missing from it are listed as new or changed; otherwise every shape is.
+#[derive(Default)]
+pub struct MarkdownReporter {
+    pub previous_shapes: Option<BTreeSet<String>>,
+}
+
+impl MarkdownReporter {
+    /// Reads query shape IDs out of an earlier `analyze --format json`
+    /// document.
+    pub fn with_previous_report(previous: &Value) -> Self {
+        let shapes = previous["collections"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|collection| collection["files"].as_array().into_iter().flatten())
+            .flat_map(|file| file["patterns"].as_array().into_iter().flatten())
+            .filter_map(|pattern| pattern["shapeId"].as_str())
+            .map(str::to_string)
+            .collect();
+
+        Self {
+            previous_shapes: Some(shapes),
+        }
+    }
+}
+
+impl Reporter for MarkdownReporter {
+    fn render(&self, report: &AnalysisReport) -> Result<String, Box<dyn Error>> {
+        let root = Path::new(&report.root);
+        let mut out = String::new();
+
+        writeln!(
+            out,
+            "### redshift: {} query sites across {} collections",
+            report.queries.len(),
+            report.collections.len()
+        )?;
+        writeln!(out)?;
+
+        let mut by_severity: BTreeMap<Severity, Vec<_>> = BTreeMap::new();
+        for warning in &report.warnings {
+            by_severity
+                .entry(warning.severity)
+                .or_default()
+                .push(warning);
+        }
+        let counts: Vec<String> = [Severity::Error, Severity::Warning, Severity::Info]
+            .iter()
+            .map(|severity| {
+                let count = by_severity.get(severity).map_or(0, Vec::len);
+                format!("{} {}", count, severity.as_str())
+            })
+            .collect();
+        writeln!(out, "**Findings:** {}", counts.join(" · "))?;
+        writeln!(out)?;
+
+        let mut shapes: BTreeMap<String, (String, Vec<&MongoQuery>)> = BTreeMap::new();
+        for query in &report.queries {
+            let shape = QueryShape::of(query);
+            shapes
+                .entry(shape.to_string())
+                .or_insert_with(|| (shape.id(), Vec::new()))
+                .1
+                .push(query);
+        }
+        let heading = match &self.previous_shapes {
+            Some(previous) => {
+                shapes.retain(|_, (id, _)| !previous.contains(id));
+                "New or changed query shapes"
+            }
+            None => "Query shapes",
+        };
+        if shapes.is_empty() {
+            writeln!(out, "No {}.", heading.to_lowercase())?;
+        } else {
+            writeln!(
+                out,
+                "<details><summary>{} ({})</summary>",
+                heading,
+                shapes.len()
+            )?;
+            writeln!(out)?;
+            writeln!(out, "| Shape | Sites |")?;
+            writeln!(out, "| --- | --- |")?;
+            for (shape, (_, queries)) in &shapes {
+                let sites: Vec<String> = queries
+                    .iter()
+                    .map(|query| format!("`{}:{}`", relative_path(root, &query.file), query.line))
+                    .collect();
+                writeln!(out, "| `{}` | {} |", escape_cell(shape), sites.join(", "))?;
+            }
+            writeln!(out)?;
+            writeln!(out, "</details>")?;
+        }
+        writeln!(out)?;
+
+        if !report.warnings.is_empty() {
+            writeln!(
+                out,
+                "<details><summary>Findings ({})</summary>",
+                report.warnings.len()
+            )?;
+            writeln!(out)?;
+            for (severity, warnings) in by_severity.iter().rev() {
+                writeln!(out, "**{}**", severity.as_str())?;
+                writeln!(out)?;
+                for warning in warnings {
+                    let location = match (&warning.file, warning.line) {
+                        (Some(file), Some(line)) => {
+                            format!("`{}:{}` ", relative_path(root, file), line)
+                        }
+                        _ => String::new(),
+                    };
+                    writeln!(
+                        out,
+                        "- {}{} (`{}`)",
+                        location,
+                        warning.message,
+                        warning.rule.id()
+                    )?;
+                }
+                writeln!(out)?;
+            }
+            writeln!(out, "</details>")?;
+            writeln!(out)?;
+        }
+
+        if !report.index_suggestions.is_empty() {
+            let total: usize = report
+                .index_suggestions
+                .iter()
+                .map(|collection| collection.suggestions.len())
+                .sum();
+            writeln!(
+                out,
+                "<details><summary>Index suggestions ({})</summary>",
+                total
+            )?;
+            writeln!(out)?;
+            for collection in &report.index_suggestions {
+                for suggestion in &collection.suggestions {
+                    writeln!(out, "- `{}`: {}", collection.collection, suggestion)?;
+                }
+            }
+            writeln!(out)?;
+            writeln!(out, "</details>")?;
+            writeln!(out)?;
+        }
+
+        writeln!(out, "<details><summary>Per-collection counts</summary>")?;
+        writeln!(out)?;
+        writeln!(
+            out,
+            "| Collection | Files | Reads | Writes | Deletes | Inserts |"
+        )?;
+        writeln!(out, "| --- | --- | --- | --- | --- | --- |")?;
+        for collection in &report.collections {
+            let mut counts = [0usize; 4];
+            for query in collection
+                .files
+                .iter()
+                .flat_map(|file| &file.patterns)
+                .flat_map(|pattern| &pattern.queries)
+            {
+                counts[match query.operation {
+                    OperationClass::Read => 0,
+                    OperationClass::Write => 1,
+                    OperationClass::Delete => 2,
+                    OperationClass::Insert => 3,
+                }] += 1;
+            }
+            writeln!(
+                out,
+                "| `{}` | {} | {} | {} | {} | {} |",
+                escape_cell(&collection.collection),
+                collection.files.len(),
+                counts[0],
+                counts[1],
+                counts[2],
+                counts[3]
+            )?;
+        }
+        writeln!(out)?;
+        writeln!(out, "</details>")?;
+
+        Ok(out)
+    }
+}
+
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::MarkdownReporter;
+    use crate::report::{AnalysisReport, Reporter};
+    use crate::MongoQuery;
+
+    fn query(line: usize, fields: &[&str]) -> MongoQuery {
+        MongoQuery {
+            file: "app/src/user.service.ts".to_string(),
+            line,
+            collection: "users".to_string(),
+            method: "find".to_string(),
+            query_fields: fields.iter().map(|field| field.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lists_only_shapes_missing_from_previous_report() {
+        let report = AnalysisReport::new(
+            Path::new("app"),
+            vec![query(3, &["email"]), query(9, &["tenantId", "email"])],
+            None,
+        );
+        let previous = AnalysisReport::new(Path::new("app"), vec![query(3, &["email"])], None);
+        let previous = serde_json::to_value(&previous).unwrap();
+
+        let markdown = MarkdownReporter::with_previous_report(&previous)
+            .render(&report)
+            .unwrap();
+
+        assert!(markdown.contains("New or changed query shapes (1)"));
+        assert!(markdown
+            .contains("| `users.find({email:$eq, tenantId:$eq})` | `src/user.service.ts:9` |"));
+        assert!(!markdown.contains("`users.find({email:$eq})`"));
+        assert!(markdown.contains("| `users` | 1 | 2 | 0 | 0 | 0 |"));
+    }
+}
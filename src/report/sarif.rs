@@ -0,0 +1,190 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use super::{AnalysisReport, Reporter};
+use crate::config::{ConfigWarning, Rule, Severity};
+use crate::{percent_encode_path, relative_path};
+
+pub struct SarifReporter;
+
+impl Reporter for SarifReporter {
+    fn render(&self, report: &AnalysisReport) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(&render(report))?)
+    }
+}
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SRCROOT: &str = "%SRCROOT%";
+
+/// Renders the report's warnings as a SARIF 2.1.0 log with one run.
+///
+/// Locations are relative to the analyzed root, exposed as `%SRCROOT%`.
+/// Project-level findings are attached to the config file when it lives under
+/// the root, and carry no location otherwise.
+pub fn render(report: &AnalysisReport) -> Value {
+    let root = Path::new(&report.root);
+
+    let rules: Vec<Value> = Rule::ALL
+        .iter()
+        .map(|rule| rule_descriptor(*rule))
+        .collect();
+    let results: Vec<Value> = report
+        .warnings
+        .iter()
+        .map(|warning| result(warning, root, report.config_path.as_deref()))
+        .collect();
+
+    let mut run = json!({
+        "tool": {
+            "driver": {
+                "name": "redshift",
+                "version": report.tool_version,
+                "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                "rules": rules,
+            }
+        },
+        "results": results,
+    });
+
+    if let Ok(absolute_root) = fs::canonicalize(root) {
+        let mut uri = format!(
+            "file://{}",
+            percent_encode_path(&absolute_root.to_string_lossy())
+        );
+        if !uri.ends_with('/') {
+            uri.push('/');
+        }
+        run["originalUriBaseIds"] = json!({ SRCROOT: { "uri": uri } });
+    }
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [run],
+    })
+}
+
+fn rule_descriptor(rule: Rule) -> Value {
+    json!({
+        "id": rule.id(),
+        "shortDescription": { "text": rule.description() },
+        "help": { "text": rule.help() },
+    })
+}
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info => "note",
+    }
+}
+
+fn result(warning: &ConfigWarning, root: &Path, config_path: Option<&str>) -> Value {
+    let rule_index = Rule::ALL
+        .iter()
+        .position(|rule| *rule == warning.rule)
+        .unwrap_or_default();
+
+    let mut result = json!({
+        "ruleId": warning.rule.id(),
+        "ruleIndex": rule_index,
+        "level": level(warning.severity),
+        "message": { "text": warning.message },
+    });
+
+    let location = match (&warning.file, warning.line) {
+        (Some(file), Some(line)) => {
+            let mut region = json!({ "startLine": line });
+            if let Some(column) = warning.column {
+                region["startColumn"] = json!(column);
+            }
+            if let Some(end_line) = warning.end_line {
+                region["endLine"] = json!(end_line);
+            }
+            if let Some(end_column) = warning.end_column {
+                region["endColumn"] = json!(end_column);
+            }
+            Some(json!({
+                "physicalLocation": {
+                    "artifactLocation": {
+                        "uri": percent_encode_path(&relative_path(root, file)),
+                        "uriBaseId": SRCROOT,
+                    },
+                    "region": region,
+                }
+            }))
+        }
+        _ => config_path
+            .filter(|config| Path::new(config).starts_with(root))
+            .map(|config| {
+                json!({
+                    "physicalLocation": {
+                        "artifactLocation": {
+                            "uri": percent_encode_path(&relative_path(root, config)),
+                            "uriBaseId": SRCROOT,
+                        }
+                    }
+                })
+            }),
+    };
+
+    if let Some(location) = location {
+        result["locations"] = json!([location]);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::render;
+    use crate::config::{ConfigWarning, Rule, Severity};
+    use crate::report::AnalysisReport;
+    use crate::MongoQuery;
+
+    #[test]
+    fn maps_warnings_to_results_with_regions() {
+        let query = MongoQuery {
+            file: "project/src/user service#1.ts".to_string(),
+            line: 12,
+            column: 5,
+            end_line: 14,
+            end_column: 7,
+            collection: "users".to_string(),
+            method: "find".to_string(),
+            ..Default::default()
+        };
+        let mut report = AnalysisReport::new(Path::new("project"), vec![query.clone()], None);
+        report.warnings = vec![ConfigWarning::at_query(
+            Rule::RecommendedPredicateMiss,
+            Severity::Info,
+            "missing tenant".to_string(),
+            &query,
+        )];
+
+        let sarif = render(&report);
+        let result = &sarif["runs"][0]["results"][0];
+
+        assert_eq!(sarif["version"], "2.1.0");
+        assert_eq!(result["ruleId"], "recommended-predicate-miss");
+        assert_eq!(result["level"], "note");
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(
+            location["artifactLocation"]["uri"],
+            "src/user%20service%231.ts"
+        );
+        assert_eq!(location["region"]["startColumn"], 5);
+        assert_eq!(location["region"]["endLine"], 14);
+        assert_eq!(
+            sarif["runs"][0]["tool"]["driver"]["rules"]
+                [result["ruleIndex"].as_u64().unwrap() as usize]["id"],
+            "recommended-predicate-miss"
+        );
+    }
+}
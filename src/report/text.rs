@@ -0,0 +1,374 @@
+use std::error::Error;
+use std::fmt::Write;
+use std::path::Path;
+
+use super::{AnalysisReport, Reporter, SourceCache};
+use crate::config::{ConfigWarning, Severity};
+use crate::{relative_path, FileCategory};
+
+/// Longest span printed in full; longer ones are elided in the middle.
+const MAX_SNIPPET_LINES: usize = 6;
+
+/// The default human-readable `analyze` output.
+pub struct TextReporter {
+    /// List every query site under its pattern.
+    pub verbose: bool,
+    /// Emit ANSI colors.
+    pub color: bool,
+}
+
+impl Reporter for TextReporter {
+    fn render(&self, report: &AnalysisReport) -> Result<String, Box<dyn Error>> {
+        let root = Path::new(&report.root);
+        let mut out = String::new();
+
+        for collection_data in &report.collections {
+            writeln!(out, "Collection: {}", collection_data.collection)?;
+
+            for file_data in &collection_data.files {
+                let marker = match file_data.category {
+                    FileCategory::Source => "",
+                    FileCategory::Test => " (test)",
+                };
+                writeln!(
+                    out,
+                    "  File: {}{}",
+                    relative_path(root, &file_data.file_path),
+                    marker
+                )?;
+
+                for pattern in &file_data.patterns {
+                    writeln!(out, "    - {}: {} usages", pattern.shape, pattern.count)?;
+
+                    if self.verbose {
+                        let mut queries = pattern.queries.clone();
+                        queries.sort_by_key(|q| (q.line, q.column));
+                        for query in queries {
+                            writeln!(
+                                out,
+                                "      {}:{} [{}]: {}",
+                                query.line, query.column, query.operation, query.raw_match
+                            )?;
+                        }
+                    }
+                }
+            }
+            writeln!(out)?;
+        }
+
+        match &report.config_path {
+            Some(config_path) => {
+                if !report.warnings.is_empty() {
+                    writeln!(out, "Config: {}", config_path)?;
+                    writeln!(out)?;
+                }
+            }
+            None => {
+                writeln!(
+                    out,
+                    "Config: no redshift.config.json found (using defaults)"
+                )?;
+                writeln!(out)?;
+            }
+        }
+
+        let findings: Vec<&ConfigWarning> = report.warnings.iter().collect();
+        out.push_str(&self.render_findings(report, &findings)?);
+        if !report.warnings.is_empty() {
+            out.push_str(&self.render_summary(report)?);
+        }
+
+        if let Some(baseline) = &report.baseline {
+            writeln!(
+                out,
+                "{}: {} findings suppressed by {}",
+                self.paint("1", "baseline"),
+                baseline.suppressed,
+                baseline.path
+            )?;
+            if !baseline.fixed.is_empty() {
+                writeln!(
+                    out,
+                    "{}: {} baseline entries no longer match; run `redshift baseline write` to drop them",
+                    self.paint("1;36", "note"),
+                    baseline.fixed.len()
+                )?;
+                for entry in &baseline.fixed {
+                    writeln!(
+                        out,
+                        "  {} [{}] {}",
+                        entry.fingerprint,
+                        entry.rule.id(),
+                        entry.message
+                    )?;
+                }
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+impl TextReporter {
+    /// Diagnostics for `warnings`, which belong to `report`.
+    pub fn render_findings(
+        &self,
+        report: &AnalysisReport,
+        warnings: &[&ConfigWarning],
+    ) -> Result<String, Box<dyn Error>> {
+        let root = Path::new(&report.root);
+        let project_file = report
+            .config_path
+            .as_deref()
+            .map(|config| relative_path(root, config));
+        let mut sources = SourceCache::default();
+        let mut out = String::new();
+        for warning in warnings {
+            self.render_diagnostic(
+                &mut out,
+                warning,
+                root,
+                project_file.as_deref(),
+                &mut sources,
+            )?;
+        }
+        Ok(out)
+    }
+
+    /// The line counting `report`'s findings by severity.
+    pub fn render_summary(&self, report: &AnalysisReport) -> Result<String, Box<dyn Error>> {
+        let count = |severity| {
+            report
+                .warnings
+                .iter()
+                .filter(|warning| warning.severity == severity)
+                .count()
+        };
+        let mut out = String::new();
+        writeln!(
+            out,
+            "{}: {} errors, {} warnings, {} info",
+            self.paint("1", "summary"),
+            count(Severity::Error),
+            count(Severity::Warning),
+            count(Severity::Info)
+        )?;
+        let resolution = &report.resolution;
+        if resolution.queries > 0 {
+            writeln!(
+                out,
+                "{}: {} of {} query sites fully resolved ({:.0}%), {} partial, {} unresolved",
+                self.paint("1", "resolution"),
+                resolution.resolved,
+                resolution.queries,
+                resolution.resolved_percent(),
+                resolution.partial,
+                resolution.unresolved
+            )?;
+        }
+        Ok(out)
+    }
+
+    /// Wraps `text` in the given SGR style when color is enabled.
+    pub fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", style, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Prints one warning the way rustc prints a diagnostic: a header with the
+    /// rule ID, the location relative to the root, the query's source lines
+    /// with the span underlined, and the rule's help.
+    fn render_diagnostic(
+        &self,
+        out: &mut String,
+        warning: &ConfigWarning,
+        root: &Path,
+        project_file: Option<&str>,
+        sources: &mut SourceCache,
+    ) -> Result<(), Box<dyn Error>> {
+        let (label, style) = match warning.severity {
+            Severity::Error => ("error", "1;31"),
+            Severity::Warning => ("warning", "1;33"),
+            Severity::Info => ("info", "1;36"),
+        };
+        let gutter_style = "1;34";
+
+        writeln!(
+            out,
+            "{}{} {}",
+            self.paint(style, &format!("{}[{}]", label, warning.rule.id())),
+            self.paint("1", ":"),
+            self.paint("1", &warning.message)
+        )?;
+
+        let lines = match (&warning.file, warning.line) {
+            (Some(file), Some(line)) => {
+                let column = warning.column.unwrap_or(1);
+                let end_line = warning.end_line.unwrap_or(line).max(line);
+                let end_column = warning.end_column.unwrap_or(column + 1);
+                let excerpt = sources.lines(file).and_then(|lines| {
+                    lines
+                        .get(line - 1..end_line.min(lines.len()))
+                        .map(<[String]>::to_vec)
+                });
+                let width = end_line.to_string().len();
+                writeln!(
+                    out,
+                    "{}{} {}:{}:{}",
+                    " ".repeat(width),
+                    self.paint(gutter_style, "-->"),
+                    relative_path(root, file),
+                    line,
+                    column
+                )?;
+                excerpt.map(|excerpt| (excerpt, line, column, end_column, width))
+            }
+            _ => {
+                if let Some(project_file) = project_file {
+                    writeln!(out, " {} {}", self.paint(gutter_style, "-->"), project_file)?;
+                }
+                None
+            }
+        };
+
+        let width = match lines {
+            Some((excerpt, line, column, end_column, width)) => {
+                let bar = self.paint(gutter_style, &format!("{} |", " ".repeat(width)));
+                writeln!(out, "{}", bar)?;
+                self.render_snippet(out, &excerpt, line, column, end_column, width, style)?;
+                width
+            }
+            None => 1,
+        };
+
+        writeln!(
+            out,
+            "{} {} {}",
+            " ".repeat(width),
+            self.paint(gutter_style, "="),
+            format_args!("{}: {}", self.paint("1", "help"), warning.rule.help())
+        )?;
+        writeln!(out)?;
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn render_snippet(
+        &self,
+        out: &mut String,
+        excerpt: &[String],
+        line: usize,
+        column: usize,
+        end_column: usize,
+        width: usize,
+        style: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let gutter = |number: Option<usize>| {
+            let number = number.map(|n| n.to_string()).unwrap_or_default();
+            self.paint("1;34", &format!("{:>width$} |", number, width = width))
+        };
+        let excerpt: Vec<String> = excerpt.iter().map(|text| text.replace('\t', " ")).collect();
+
+        if let [only] = excerpt.as_slice() {
+            let start = column.saturating_sub(1);
+            let carets = end_column.saturating_sub(column).max(1);
+            writeln!(out, "{} {}", gutter(Some(line)), only)?;
+            writeln!(
+                out,
+                "{} {}{}",
+                gutter(None),
+                " ".repeat(start),
+                self.paint(style, &"^".repeat(carets))
+            )?;
+            return Ok(());
+        }
+
+        // Multi-line spans: an underscore line pointing at the start column,
+        // a `|` down the left margin, and a closing line ending at the last
+        // character of the span.
+        let last = excerpt.len() - 1;
+        writeln!(out, "{}   {}", gutter(Some(line)), excerpt[0])?;
+        writeln!(
+            out,
+            "{}  {}",
+            gutter(None),
+            self.paint(style, &format!("{}^", "_".repeat(column)))
+        )?;
+        for (offset, text) in excerpt.iter().enumerate().skip(1) {
+            if excerpt.len() > MAX_SNIPPET_LINES && offset > 2 && offset < last - 1 {
+                if offset == 3 {
+                    writeln!(out, "{}", self.paint("1;34", "..."))?;
+                }
+                continue;
+            }
+            writeln!(
+                out,
+                "{} {} {}",
+                gutter(Some(line + offset)),
+                self.paint(style, "|"),
+                text
+            )?;
+        }
+        writeln!(
+            out,
+            "{} {}",
+            gutter(None),
+            self.paint(
+                style,
+                &format!("|{}^", "_".repeat(end_column.saturating_sub(1)))
+            )
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TextReporter;
+    use crate::config::{ConfigWarning, Rule, Severity};
+    use crate::report::{AnalysisReport, Reporter};
+    use crate::{MongoQuery, TestDir};
+
+    #[test]
+    fn renders_caret_diagnostics_relative_to_root() {
+        let dir = TestDir::new("text-reporter");
+        let file = dir.write(
+            "src/user.service.ts",
+            "const users = db.collection('users');\nusers.find({ email });\n",
+        );
+
+        let query = MongoQuery {
+            file: file.display().to_string(),
+            line: 2,
+            column: 1,
+            end_line: 2,
+            end_column: 22,
+            collection: "users".to_string(),
+            method: "find".to_string(),
+            ..Default::default()
+        };
+        let mut report = AnalysisReport::new(dir.path(), vec![query.clone()], None);
+        report.warnings = vec![ConfigWarning::at_query(
+            Rule::RecommendedPredicateMiss,
+            Severity::Warning,
+            "missing tenant".to_string(),
+            &query,
+        )];
+
+        let text = TextReporter {
+            verbose: false,
+            color: false,
+        }
+        .render(&report)
+        .unwrap();
+
+        assert!(text.contains("  File: src/user.service.ts\n"));
+        assert!(text.contains("warning[recommended-predicate-miss]: missing tenant\n"));
+        assert!(text.contains(" --> src/user.service.ts:2:1\n"));
+        assert!(text.contains("2 | users.find({ email });\n  | ^^^^^^^^^^^^^^^^^^^^^\n"));
+        assert!(!text.contains('\x1b'));
+    }
+}
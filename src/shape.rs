@@ -0,0 +1,289 @@
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::baseline::fingerprint;
+use crate::{MongoQuery, Projection};
+
+pub const LOCK_FILE_NAME: &str = "redshift.lock.json";
+
+/// Version of the lockfile format.
+pub const LOCK_VERSION: u32 = 1;
+
+/// A query with its literal values and location stripped: which collection
+/// and method it uses, which operators it applies to which fields, and how it
+/// sorts, projects and pipelines. Two call sites with the same shape put the
+/// same demands on indexes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QueryShape {
+    pub collection: String,
+    pub method: String,
+    /// Sorted `field:$operator` terms.
+    pub predicate: Vec<String>,
+    /// `field:order` in sort order.
+    pub sort: Vec<String>,
+    pub projection: Option<Projection>,
+    pub pipeline: Vec<String>,
+}
+
+impl QueryShape {
+    pub fn of(query: &MongoQuery) -> Self {
+        let mut predicate = query.predicate.clone();
+        // Queries recorded without terms (e.g. built by hand) still carry
+        // their fields.
+        if predicate.is_empty() {
+            predicate = query
+                .query_fields
+                .iter()
+                .map(|field| format!("{}:$eq", field))
+                .collect();
+        }
+        predicate.sort();
+        predicate.dedup();
+
+        Self {
+            collection: query.collection.clone(),
+            method: query.method.clone(),
+            predicate,
+            sort: query
+                .options
+                .sort
+                .iter()
+                .map(|key| format!("{}:{}", key.field, key.order))
+                .collect(),
+            projection: query.projection.clone(),
+            pipeline: query.pipeline.clone(),
+        }
+    }
+
+    /// Stable 16-hex-digit ID of the shape.
+    pub fn id(&self) -> String {
+        let projection = self
+            .projection
+            .as_ref()
+            .map(|projection| {
+                format!(
+                    "{}/{}",
+                    projection.include.join(","),
+                    projection.exclude.join(",")
+                )
+            })
+            .unwrap_or_default();
+        fingerprint(&[
+            &self.collection,
+            &self.method,
+            &self.predicate.join(","),
+            &self.sort.join(","),
+            &projection,
+            &self.pipeline.join(","),
+        ])
+    }
+}
+
+impl fmt::Display for QueryShape {
+    /// `users.find({email:$eq, age:$gt}).sort({age:-1}).project({name})`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.pipeline.is_empty() {
+            write!(
+                f,
+                "{}.{}({{{}}})",
+                self.collection,
+                self.method,
+                self.predicate.join(", ")
+            )?;
+        } else {
+            write!(
+                f,
+                "{}.{}([{}])",
+                self.collection,
+                self.method,
+                self.pipeline.join(", ")
+            )?;
+            if !self.predicate.is_empty() {
+                write!(f, ".match({{{}}})", self.predicate.join(", "))?;
+            }
+        }
+        if !self.sort.is_empty() {
+            write!(f, ".sort({{{}}})", self.sort.join(", "))?;
+        }
+        if let Some(projection) = &self.projection {
+            let fields: Vec<String> = projection
+                .include
+                .iter()
+                .cloned()
+                .chain(projection.exclude.iter().map(|field| format!("-{}", field)))
+                .collect();
+            write!(f, ".project({{{}}})", fields.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InventoryEntry {
+    pub id: String,
+    pub collection: String,
+    pub shape: String,
+}
+
+/// The reviewed set of query shapes, committed as `redshift.lock.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Inventory {
+    pub version: u32,
+    /// Ordered by collection, then shape.
+    pub shapes: Vec<InventoryEntry>,
+}
+
+/// Shapes that differ between an inventory and the current code.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct InventoryDrift {
+    /// Shapes in the code but not in the inventory: new queries, or changed
+    /// ones whose old shape shows up in `removed`.
+    pub added: Vec<InventoryEntry>,
+    pub removed: Vec<InventoryEntry>,
+}
+
+impl InventoryDrift {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl Inventory {
+    pub fn from_queries(queries: &[MongoQuery]) -> Self {
+        let shapes: BTreeMap<(String, String), String> = queries
+            .iter()
+            .map(|query| {
+                let shape = QueryShape::of(query);
+                ((shape.collection.clone(), shape.to_string()), shape.id())
+            })
+            .collect();
+
+        Self {
+            version: LOCK_VERSION,
+            shapes: shapes
+                .into_iter()
+                .map(|((collection, shape), id)| InventoryEntry {
+                    id,
+                    collection,
+                    shape,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path).map_err(|err| {
+            std::io::Error::new(
+                err.kind(),
+                format!("Failed to read {}: {}", path.display(), err),
+            )
+        })?;
+        let inventory: Self = serde_json::from_str(&content).map_err(|err| {
+            std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Failed to parse {}: {}", path.display(), err),
+            )
+        })?;
+        if inventory.version != LOCK_VERSION {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Unsupported lockfile version {} in {}",
+                    inventory.version,
+                    path.display()
+                ),
+            )
+            .into());
+        }
+        Ok(inventory)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut content = serde_json::to_string_pretty(self)?;
+        content.push('\n');
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Compares the reviewed inventory (`self`) with `current`.
+    pub fn drift(&self, current: &Inventory) -> InventoryDrift {
+        let reviewed: HashSet<&str> = self.shapes.iter().map(|entry| entry.id.as_str()).collect();
+        let present: HashSet<&str> = current
+            .shapes
+            .iter()
+            .map(|entry| entry.id.as_str())
+            .collect();
+
+        InventoryDrift {
+            added: current
+                .shapes
+                .iter()
+                .filter(|entry| !reviewed.contains(entry.id.as_str()))
+                .cloned()
+                .collect(),
+            removed: self
+                .shapes
+                .iter()
+                .filter(|entry| !present.contains(entry.id.as_str()))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Inventory, QueryShape};
+    use crate::ast_parser::parse_file;
+
+    #[test]
+    fn shape_ignores_values_and_order_but_not_operators() {
+        let source = r#"
+            db.collection('orders').find({ status: 'open', total: { $gt: 10 } }).sort({ createdAt: -1 });
+            db.collection('orders').find({ total: { $gt: 99 }, status: state }).sort({ createdAt: -1 });
+            db.collection('orders').find({ status: 'open', total: { $lt: 10 } }).sort({ createdAt: -1 });
+            db.collection('orders').aggregate([{ $match: { status: 'open' } }, { $group: { _id: '$userId' } }]);
+        "#;
+
+        let shapes: Vec<QueryShape> = parse_file(source, "orders.ts")
+            .iter()
+            .map(QueryShape::of)
+            .collect();
+
+        assert_eq!(shapes[0].id(), shapes[1].id());
+        assert_ne!(shapes[0].id(), shapes[2].id());
+        assert_eq!(
+            shapes[0].to_string(),
+            "orders.find({status:$eq, total:$gt}).sort({createdAt:-1})"
+        );
+        assert_eq!(
+            shapes[3].to_string(),
+            "orders.aggregate([$match, $group]).match({status:$eq})"
+        );
+    }
+
+    #[test]
+    fn drift_lists_unreviewed_and_vanished_shapes() {
+        let reviewed = Inventory::from_queries(&parse_file(
+            "db.collection('orders').find({ status: 'open' });",
+            "orders.ts",
+        ));
+        let current = Inventory::from_queries(&parse_file(
+            "db.collection('orders').find({ status: { $in: states } });",
+            "orders.ts",
+        ));
+
+        let drift = reviewed.drift(&current);
+        assert_eq!(drift.added[0].shape, "orders.find({status:$in})");
+        assert_eq!(drift.removed[0].shape, "orders.find({status:$eq})");
+        assert!(reviewed.drift(&reviewed).is_empty());
+    }
+}
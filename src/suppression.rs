@@ -0,0 +1,304 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use swc_core::common::comments::{Comment, CommentKind};
+use swc_core::common::SourceMap;
+
+use crate::config::{ConfigWarning, Rule, Severity};
+use crate::relative_path;
+
+const IGNORE_NEXT_LINE: &str = "redshift-ignore-next-line";
+const DISABLE: &str = "redshift-disable";
+const ENABLE: &str = "redshift-enable";
+
+/// An inline comment that hides findings of some rules on some lines of one
+/// file:
+///
+/// ```ts
+/// // redshift-ignore-next-line recommended-predicate-miss: admin-only lookup
+/// /* redshift-disable full-document-read: export job */ ... /* redshift-enable */
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Suppression {
+    pub file: String,
+    /// 1-based position of the comment itself.
+    pub line: usize,
+    pub column: usize,
+    /// First and last suppressed lines, inclusive.
+    pub start_line: usize,
+    pub end_line: usize,
+    /// Rule IDs as written.
+    pub rules: Vec<String>,
+    /// Text after the `:`, if any.
+    pub reason: Option<String>,
+}
+
+impl Suppression {
+    fn covers(&self, warning: &ConfigWarning) -> bool {
+        let (Some(file), Some(line)) = (&warning.file, warning.line) else {
+            return false;
+        };
+        *file == self.file
+            && (self.start_line..=self.end_line).contains(&line)
+            && self.rules.iter().any(|rule| rule == warning.rule.id())
+    }
+
+    fn describe(&self) -> String {
+        self.rules.join(", ")
+    }
+}
+
+/// Reads suppression directives out of a file's comments.
+pub fn from_comments(
+    comments: &[Comment],
+    source_map: &SourceMap,
+    file_path: &str,
+) -> Vec<Suppression> {
+    let mut comments: Vec<&Comment> = comments.iter().collect();
+    comments.sort_by_key(|comment| comment.span.lo);
+
+    let mut suppressions = Vec::new();
+    let mut open: Vec<usize> = Vec::new();
+
+    for comment in comments {
+        let text = match comment.kind {
+            CommentKind::Line => comment.text.trim(),
+            CommentKind::Block => comment.text.trim().trim_start_matches('*').trim(),
+        };
+        let start = source_map.lookup_char_pos(comment.span.lo);
+        let end = source_map.lookup_char_pos(comment.span.hi);
+
+        if let Some((rules, reason)) = text
+            .strip_prefix(IGNORE_NEXT_LINE)
+            .and_then(parse_directive)
+        {
+            suppressions.push(Suppression {
+                file: file_path.to_string(),
+                line: start.line,
+                column: start.col.0 + 1,
+                start_line: end.line + 1,
+                end_line: end.line + 1,
+                rules,
+                reason,
+            });
+        } else if let Some((rules, reason)) = text.strip_prefix(DISABLE).and_then(parse_directive) {
+            open.push(suppressions.len());
+            suppressions.push(Suppression {
+                file: file_path.to_string(),
+                line: start.line,
+                column: start.col.0 + 1,
+                start_line: end.line,
+                end_line: usize::MAX,
+                rules,
+                reason,
+            });
+        } else if let Some((rules, _)) = text.strip_prefix(ENABLE).and_then(parse_directive) {
+            // `redshift-enable` closes every open range; `redshift-enable <rule>`
+            // only those disabling that rule.
+            open.retain(|&index| {
+                let suppression = &mut suppressions[index];
+                let closes =
+                    rules.is_empty() || suppression.rules.iter().any(|rule| rules.contains(rule));
+                if closes {
+                    suppression.end_line = start.line;
+                }
+                !closes
+            });
+        }
+    }
+
+    suppressions
+}
+
+/// Splits ` rule-a, rule-b: reason` into rule IDs and the optional reason.
+/// Returns `None` when the directive word continues, as in
+/// `redshift-disabled`.
+fn parse_directive(directive: &str) -> Option<(Vec<String>, Option<String>)> {
+    if !directive.is_empty() && !directive.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let (rules, reason) = match directive.split_once(':') {
+        Some((rules, reason)) => (rules, Some(reason.trim())),
+        None => (directive, None),
+    };
+    let rules = rules
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|rule| !rule.is_empty())
+        .map(str::to_string)
+        .collect();
+    let reason = reason
+        .filter(|reason| !reason.is_empty())
+        .map(str::to_string);
+    Some((rules, reason))
+}
+
+/// Drops the findings hidden by `suppressions`, then reports suppressions
+/// that give no reason or no longer hide anything, so they do not rot.
+///
+/// Without `config_rules_evaluated` only `parse-error` findings exist, so a
+/// suppression of any other rule is not reported as unused.
+pub fn apply(
+    warnings: Vec<ConfigWarning>,
+    suppressions: &[Suppression],
+    root: &Path,
+    config_rules_evaluated: bool,
+) -> Vec<ConfigWarning> {
+    let mut used = HashSet::new();
+    let mut remaining: Vec<ConfigWarning> = warnings
+        .into_iter()
+        .filter(|warning| {
+            let hiding: Vec<usize> = suppressions
+                .iter()
+                .enumerate()
+                .filter(|(_, suppression)| suppression.covers(warning))
+                .map(|(index, _)| index)
+                .collect();
+            used.extend(hiding.iter().copied());
+            hiding.is_empty()
+        })
+        .collect();
+
+    for (index, suppression) in suppressions.iter().enumerate() {
+        // Fingerprinted by file, so the same suppression in two files is two
+        // baseline entries.
+        let key = format!(
+            "{}:{}",
+            relative_path(root, &suppression.file),
+            suppression.describe()
+        );
+        if suppression.reason.is_none() {
+            remaining.push(ConfigWarning::at_location(
+                Rule::SuppressionWithoutReason,
+                Severity::Warning,
+                format!(
+                    "Suppression of [{}] gives no reason; add one after ':'",
+                    suppression.describe()
+                ),
+                &suppression.file,
+                suppression.line,
+                suppression.column,
+                &key,
+            ));
+        }
+        if !used.contains(&index) {
+            let unknown: Vec<&str> = suppression
+                .rules
+                .iter()
+                .map(String::as_str)
+                .filter(|rule| Rule::from_id(rule).is_none())
+                .collect();
+            let evaluated = config_rules_evaluated
+                || suppression
+                    .rules
+                    .iter()
+                    .all(|rule| rule == Rule::ParseError.id());
+            let message = if suppression.rules.is_empty() {
+                "Suppression names no rule".to_string()
+            } else if !unknown.is_empty() {
+                format!("Suppression names unknown rule(s) [{}]", unknown.join(", "))
+            } else if evaluated {
+                format!(
+                    "Suppression of [{}] does not match any finding",
+                    suppression.describe()
+                )
+            } else {
+                continue;
+            };
+            remaining.push(ConfigWarning::at_location(
+                Rule::UnusedSuppression,
+                Severity::Warning,
+                message,
+                &suppression.file,
+                suppression.line,
+                suppression.column,
+                &key,
+            ));
+        }
+    }
+
+    remaining
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::apply;
+    use crate::ast_parser::parse_source;
+    use crate::config::{ConfigWarning, Rule, Severity};
+
+    #[test]
+    fn hides_covered_findings_and_reports_rotting_suppressions() {
+        let source = r#"
+            // redshift-ignore-next-line recommended-predicate-miss: admin lookup
+            db.collection('users').find({ email });
+            /* redshift-disable full-document-read */
+            db.collection('users').find({ role });
+            /* redshift-enable */
+            // redshift-ignore-next-line full-document-read: stale
+            db.collection('users').find({ name });
+        "#;
+        let parsed = parse_source(source, "users.ts");
+        assert_eq!(parsed.suppressions.len(), 3);
+
+        let warnings = parsed
+            .queries
+            .iter()
+            .map(|query| {
+                let rule = if query.line == 3 {
+                    Rule::RecommendedPredicateMiss
+                } else if query.line == 5 {
+                    Rule::FullDocumentRead
+                } else {
+                    Rule::RecommendedPredicateMiss
+                };
+                ConfigWarning::at_query(rule, Severity::Warning, String::new(), query)
+            })
+            .collect();
+
+        let remaining = apply(warnings, &parsed.suppressions, Path::new(""), true);
+        let found: Vec<(Rule, Option<usize>)> = remaining
+            .iter()
+            .map(|warning| (warning.rule, warning.line))
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                (Rule::RecommendedPredicateMiss, Some(8)),
+                (Rule::SuppressionWithoutReason, Some(4)),
+                (Rule::UnusedSuppression, Some(7)),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unused_suppressions_per_file_and_only_for_evaluated_rules() {
+        let source =
+            "// redshift-ignore-next-line full-document-read\ndb.collection('users').find({});";
+        let mut suppressions = parse_source(source, "/repo/src/a.ts").suppressions;
+        suppressions.extend(parse_source(source, "/repo/src/b.ts").suppressions);
+        let root = Path::new("/repo");
+
+        let remaining = apply(Vec::new(), &suppressions, root, true);
+        let unused: Vec<&str> = remaining
+            .iter()
+            .filter(|warning| warning.rule == Rule::UnusedSuppression)
+            .map(|warning| warning.fingerprint.as_str())
+            .collect();
+        assert_eq!(unused.len(), 2);
+        assert_ne!(unused[0], unused[1]);
+
+        let remaining = apply(Vec::new(), &suppressions, root, false);
+        let rules: Vec<Rule> = remaining.iter().map(|warning| warning.rule).collect();
+        assert_eq!(
+            rules,
+            vec![
+                Rule::SuppressionWithoutReason,
+                Rule::SuppressionWithoutReason
+            ]
+        );
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::ast_parser::{parse_source, ParsedFile};
+use crate::config::FilesConfig;
+use crate::discovery::{category_of, find_source_files};
+use crate::models::{resolve_base, ABSENT_MODULE};
+use crate::{parse_files, relative_path, FileCategory, ProjectAnalysis, ScanOptions};
+
+/// Modification time and size; a file whose stamp differs is re-parsed.
+type Stamp = Option<(SystemTime, u64)>;
+
+/// The last modification stamp of `path`, or `None` if it cannot be read.
+pub fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+struct WatchedFile {
+    stamp: Stamp,
+    category: FileCategory,
+    parsed: ParsedFile,
+}
+
+/// An open document with unsaved edits.
+struct Document {
+    text: String,
+    parsed: ParsedFile,
+}
+
+/// What was extracted from every file of a project, kept in memory and
+/// brought up to date by [`ProjectModel::refresh`], which re-parses only
+/// files whose modification time or size changed, and the files importing
+/// models from them.
+pub struct ProjectModel {
+    root: PathBuf,
+    jobs: usize,
+    selection: FilesConfig,
+    files: BTreeMap<PathBuf, WatchedFile>,
+    /// The files whose results depend on each other file, from
+    /// [`ParsedFile::dependencies`].
+    dependents: BTreeMap<PathBuf, BTreeSet<PathBuf>>,
+    /// Documents with unsaved edits, used in place of what is on disk.
+    documents: BTreeMap<PathBuf, Document>,
+}
+
+impl ProjectModel {
+    /// Parses every file under `root`.
+    pub fn load(root: &Path, options: &ScanOptions) -> Self {
+        let mut model = Self {
+            root: root.to_path_buf(),
+            jobs: options.jobs,
+            selection: options.files.clone(),
+            files: BTreeMap::new(),
+            dependents: BTreeMap::new(),
+            documents: BTreeMap::new(),
+        };
+        model.refresh();
+        model
+    }
+
+    /// Changes which files are part of the project, from the next refresh.
+    pub fn set_selection(&mut self, selection: FilesConfig) {
+        self.selection = selection;
+    }
+
+    /// Re-parses files added or modified since the last refresh, and those
+    /// importing models from a file that changed, was deleted or now exists,
+    /// and forgets deleted files. Returns the paths that changed or were
+    /// re-parsed, in order.
+    pub fn refresh(&mut self) -> Vec<PathBuf> {
+        let current: BTreeMap<PathBuf, (Stamp, FileCategory)> =
+            find_source_files(&self.root, &self.selection)
+                .into_iter()
+                .map(|file| {
+                    let stamp = stamp(&file.path);
+                    (file.path, (stamp, file.category))
+                })
+                .collect();
+
+        let deleted: Vec<PathBuf> = self
+            .files
+            .keys()
+            .filter(|path| !current.contains_key(*path))
+            .cloned()
+            .collect();
+        let modified: Vec<PathBuf> = current
+            .iter()
+            .filter(|(path, (stamp, category))| {
+                self.files
+                    .get(*path)
+                    .is_none_or(|file| file.stamp != *stamp || file.category != *category)
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        let mut touched: BTreeSet<PathBuf> = deleted.iter().chain(&modified).cloned().collect();
+        touched.extend(self.created_modules());
+        let stale: Vec<PathBuf> = touched
+            .iter()
+            .filter_map(|path| self.dependents.get(path))
+            .flatten()
+            .filter(|path| current.contains_key(*path) && !modified.contains(path))
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        for path in &deleted {
+            if let Some(file) = self.files.remove(path) {
+                self.unlink(path, &file.parsed);
+            }
+        }
+        let reparsed: Vec<PathBuf> = modified.iter().chain(&stale).cloned().collect();
+        for (path, parsed) in reparsed.iter().zip(parse_files(&reparsed, self.jobs, None)) {
+            // Unreadable files are kept, empty, so they are not retried until
+            // they change again.
+            let mut parsed = parsed.map(|(parsed, _)| parsed).unwrap_or_else(|e| {
+                eprintln!("Warning: Failed to read file {}: {}", path.display(), e);
+                ParsedFile::default()
+            });
+            let (stamp, category) = current[path];
+            parsed.set_category(category);
+            if let Some(previous) = self.files.remove(path) {
+                self.unlink(path, &previous.parsed);
+            }
+            self.link(path, &parsed);
+            self.files.insert(
+                path.clone(),
+                WatchedFile {
+                    stamp,
+                    category,
+                    parsed,
+                },
+            );
+        }
+
+        // Open documents are re-parsed from their text when what they import
+        // changed on disk.
+        let stale_documents: Vec<PathBuf> = self
+            .documents
+            .iter()
+            .filter(|(_, document)| {
+                document
+                    .parsed
+                    .dependencies
+                    .keys()
+                    .any(|dependency| touched.contains(Path::new(dependency)))
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in stale_documents {
+            let text = self.documents.remove(&path).map(|document| document.text);
+            self.set_document(path, text.unwrap_or_default());
+        }
+
+        let mut changed: Vec<PathBuf> = deleted;
+        changed.extend(reparsed);
+        changed.sort();
+        changed
+    }
+
+    /// Modules imported by some file that resolved to nothing then, and do
+    /// now.
+    fn created_modules(&self) -> Vec<PathBuf> {
+        let parsed = self
+            .files
+            .values()
+            .map(|file| &file.parsed)
+            .chain(self.documents.values().map(|document| &document.parsed));
+        let mut created = BTreeSet::new();
+        for parsed in parsed {
+            for (dependency, hash) in &parsed.dependencies {
+                if hash == ABSENT_MODULE && resolve_base(Path::new(dependency)).is_some() {
+                    created.insert(PathBuf::from(dependency));
+                }
+            }
+        }
+        created.into_iter().collect()
+    }
+
+    /// Records `path` as a dependent of each of `parsed`'s dependencies.
+    fn link(&mut self, path: &Path, parsed: &ParsedFile) {
+        for dependency in parsed.dependencies.keys() {
+            self.dependents
+                .entry(PathBuf::from(dependency))
+                .or_default()
+                .insert(path.to_path_buf());
+        }
+    }
+
+    /// Undoes [`Self::link`].
+    fn unlink(&mut self, path: &Path, parsed: &ParsedFile) {
+        for dependency in parsed.dependencies.keys() {
+            let dependency = Path::new(dependency);
+            if let Some(dependents) = self.dependents.get_mut(dependency) {
+                dependents.remove(path);
+                if dependents.is_empty() {
+                    self.dependents.remove(dependency);
+                }
+            }
+        }
+    }
+
+    /// Parses `text`, e.g. an editor's unsaved text, and uses it in place of
+    /// what was parsed from `path` on disk until the document is closed.
+    /// Test documents are parsed even when tests are excluded, but then
+    /// yield nothing.
+    pub fn set_document(&mut self, path: PathBuf, text: String) {
+        let file = path.to_string_lossy().into_owned();
+        let parsed = match category_of(&relative_path(&self.root, &file), &self.selection) {
+            Some(category) => {
+                let mut parsed = parse_source(&text, &file);
+                parsed.set_category(category);
+                parsed
+            }
+            None => ParsedFile::default(),
+        };
+        self.documents.insert(path, Document { text, parsed });
+    }
+
+    /// Goes back to what was parsed from `path` on disk.
+    pub fn close_document(&mut self, path: &Path) {
+        self.documents.remove(path);
+    }
+
+    /// Paths of the documents set with [`Self::set_document`].
+    pub fn documents(&self) -> impl Iterator<Item = &Path> {
+        self.documents.keys().map(PathBuf::as_path)
+    }
+
+    /// The project as of the last refresh, with open documents in place of
+    /// what was parsed from disk.
+    pub fn project(&self) -> ProjectAnalysis {
+        let on_disk = self
+            .files
+            .iter()
+            .filter(|(path, _)| !self.documents.contains_key(*path))
+            .map(|(_, file)| file.parsed.clone());
+        let documents = self
+            .documents
+            .values()
+            .map(|document| document.parsed.clone());
+        ProjectAnalysis::from_files(on_disk.chain(documents))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::ProjectModel;
+    use crate::{ScanOptions, TestDir};
+
+    #[test]
+    fn refresh_reparses_only_changed_files() {
+        let dir = TestDir::new("watch");
+        let users = dir.write("users.ts", "db.collection('users').find({ email });");
+        let orders = dir.write("orders.ts", "db.collection('orders').find({});");
+
+        let mut model = ProjectModel::load(dir.path(), &ScanOptions::default());
+        assert_eq!(model.project().queries.len(), 2);
+        assert!(model.refresh().is_empty());
+
+        fs::write(
+            &users,
+            "db.collection('users').find({ email }); db.collection('users').find({ role });",
+        )
+        .unwrap();
+        fs::remove_file(&orders).unwrap();
+
+        let changed = model.refresh();
+        assert_eq!(changed, vec![orders, users.clone()]);
+        let collections = |model: &ProjectModel| -> Vec<String> {
+            model
+                .project()
+                .queries
+                .into_iter()
+                .map(|query| query.collection)
+                .collect()
+        };
+        assert_eq!(collections(&model), vec!["users", "users"]);
+
+        model.set_document(
+            users.clone(),
+            "db.collection('accounts').find({});".to_string(),
+        );
+        assert_eq!(collections(&model), vec!["accounts"]);
+        model.close_document(&users);
+        assert_eq!(collections(&model), vec!["users", "users"]);
+    }
+
+    #[test]
+    fn refresh_reparses_files_importing_changed_models() {
+        let dir = TestDir::new("watch-dependencies");
+        let model_file = dir.write("user.ts", "export const User = model('User', schema);");
+        let users = dir.write(
+            "users.ts",
+            "import { User } from './user';\nimport { Order } from './order';\nUser.find({ email });\nOrder.find({});",
+        );
+        let collections = |model: &ProjectModel| -> Vec<String> {
+            model
+                .project()
+                .queries
+                .into_iter()
+                .map(|query| query.collection)
+                .collect()
+        };
+
+        let mut model = ProjectModel::load(dir.path(), &ScanOptions::default());
+        assert_eq!(collections(&model), vec!["User"]);
+
+        fs::write(&model_file, "export const User = model('Account', schema);").unwrap();
+        assert_eq!(model.refresh(), vec![model_file.clone(), users.clone()]);
+        assert_eq!(collections(&model), vec!["Account"]);
+
+        let order = dir.write("order.ts", "export const Order = model('Order', schema);");
+        assert_eq!(model.refresh(), vec![order.clone(), users.clone()]);
+        assert_eq!(collections(&model), vec!["Account", "Order"]);
+
+        fs::remove_file(&order).unwrap();
+        assert_eq!(model.refresh(), vec![order, users]);
+        assert_eq!(collections(&model), vec!["Account"]);
+    }
+}